@@ -1,4 +1,5 @@
-use crate::physics::Vec3;
+use crate::physics::{SofteningKernel, Vec3};
+use serde::{Deserialize, Serialize};
 
 const MAX_DEPTH: usize = 20;
 
@@ -6,6 +7,9 @@ pub struct OctreeNode {
     center: Vec3,
     half_size: f64,
     total_mass: f64,
+    // Sum of |mass|. Weights the center of mass so it stays well defined when
+    // signed masses nearly cancel (negative-mass mode).
+    abs_mass: f64,
     center_of_mass: Vec3,
     body_index: Option<usize>,
     children: [Option<Box<OctreeNode>>; 8],
@@ -17,6 +21,7 @@ impl OctreeNode {
             center,
             half_size,
             total_mass: 0.0,
+            abs_mass: 0.0,
             center_of_mass: Vec3::zero(),
             body_index: None,
             children: Default::default(),
@@ -40,26 +45,31 @@ impl OctreeNode {
         )
     }
 
+    fn accumulate(&mut self, pos: &Vec3, mass: f64) {
+        let weight = mass.abs();
+        let new_abs = self.abs_mass + weight;
+        if new_abs > 0.0 {
+            self.center_of_mass = Vec3::new(
+                (self.center_of_mass.x * self.abs_mass + pos.x * weight) / new_abs,
+                (self.center_of_mass.y * self.abs_mass + pos.y * weight) / new_abs,
+                (self.center_of_mass.z * self.abs_mass + pos.z * weight) / new_abs,
+            );
+        }
+        self.abs_mass = new_abs;
+        self.total_mass += mass;
+    }
+
     pub fn insert(&mut self, idx: usize, pos: &Vec3, mass: f64, depth: usize) {
         if depth >= MAX_DEPTH {
             // Just accumulate mass at this node
-            let new_mass = self.total_mass + mass;
-            if new_mass > 0.0 {
-                self.center_of_mass = Vec3::new(
-                    (self.center_of_mass.x * self.total_mass + pos.x * mass) / new_mass,
-                    (self.center_of_mass.y * self.total_mass + pos.y * mass) / new_mass,
-                    (self.center_of_mass.z * self.total_mass + pos.z * mass) / new_mass,
-                );
-            }
-            self.total_mass = new_mass;
+            self.accumulate(pos, mass);
             return;
         }
 
-        if self.total_mass == 0.0 && self.body_index.is_none() {
+        if self.abs_mass == 0.0 && self.body_index.is_none() {
             // Empty leaf: store this body
             self.body_index = Some(idx);
-            self.total_mass = mass;
-            self.center_of_mass = *pos;
+            self.accumulate(pos, mass);
             return;
         }
 
@@ -70,25 +80,19 @@ impl OctreeNode {
 
             // Reset this node
             self.total_mass = 0.0;
+            self.abs_mass = 0.0;
             self.center_of_mass = Vec3::zero();
 
-            // Re-insert existing body
+            // Re-insert existing body and keep it in this node's aggregate
             self.insert_into_child(existing_idx, &existing_pos, existing_mass, depth);
+            self.accumulate(&existing_pos, existing_mass);
         }
 
         // Insert new body into appropriate child
         self.insert_into_child(idx, pos, mass, depth);
 
         // Update aggregate
-        let new_mass = self.total_mass + mass;
-        if new_mass > 0.0 {
-            self.center_of_mass = Vec3::new(
-                (self.center_of_mass.x * self.total_mass + pos.x * mass) / new_mass,
-                (self.center_of_mass.y * self.total_mass + pos.y * mass) / new_mass,
-                (self.center_of_mass.z * self.total_mass + pos.z * mass) / new_mass,
-            );
-        }
-        self.total_mass = new_mass;
+        self.accumulate(pos, mass);
     }
 
     fn insert_into_child(&mut self, idx: usize, pos: &Vec3, mass: f64, depth: usize) {
@@ -101,15 +105,16 @@ impl OctreeNode {
         child.insert(idx, pos, mass, depth + 1);
     }
 
-    pub fn compute_acceleration(
+    // `leaf_softening_sq` gives the softening used against an individual body
+    // (so it can depend on the pair); aggregated nodes use `params.softening_sq`.
+    pub fn compute_acceleration<F: Fn(usize) -> f64>(
         &self,
         pos: &Vec3,
         body_index: usize,
-        g: f64,
-        softening_sq: f64,
-        theta: f64,
+        params: &ForceParams,
+        leaf_softening_sq: &F,
     ) -> Vec3 {
-        if self.total_mass == 0.0 {
+        if self.abs_mass == 0.0 {
             return Vec3::zero();
         }
 
@@ -118,65 +123,191 @@ impl OctreeNode {
             if leaf_idx == body_index {
                 return Vec3::zero();
             }
-            return direct_accel(pos, &self.center_of_mass, self.total_mass, g, softening_sq);
+            let leaf_sq = leaf_softening_sq(leaf_idx);
+            return direct_accel(pos, &self.center_of_mass, self.total_mass, params, leaf_sq);
         }
 
         // Check Barnes-Hut criterion: s/d < theta
         let diff = self.center_of_mass - *pos;
-        let dist_sq = diff.x * diff.x + diff.y * diff.y + diff.z * diff.z + softening_sq;
+        let dist_sq = diff.x * diff.x + diff.y * diff.y + diff.z * diff.z + params.softening_sq;
         let s = self.half_size * 2.0;
 
-        if s * s < theta * theta * dist_sq {
+        if s * s < params.theta * params.theta * dist_sq {
             // Far enough: treat as single body
-            return direct_accel(pos, &self.center_of_mass, self.total_mass, g, softening_sq);
+            return direct_accel(
+                pos,
+                &self.center_of_mass,
+                self.total_mass,
+                params,
+                params.softening_sq,
+            );
         }
 
         // Recurse into children
         let mut accel = Vec3::zero();
         for child in &self.children {
             if let Some(c) = child {
-                accel += c.compute_acceleration(pos, body_index, g, softening_sq, theta);
+                accel += c.compute_acceleration(pos, body_index, params, leaf_softening_sq);
             }
         }
         accel
     }
 }
 
-fn direct_accel(pos: &Vec3, other_pos: &Vec3, other_mass: f64, g: f64, softening_sq: f64) -> Vec3 {
+impl OctreeNode {
+    // The same walk as `compute_acceleration`, but every leaf or accepted
+    // node it sums is pushed to `out` instead
+    pub fn contributions<F: Fn(usize) -> f64>(
+        &self,
+        pos: &Vec3,
+        body_index: usize,
+        params: &ForceParams,
+        leaf_softening_sq: &F,
+        out: &mut Vec<TreeTerm>,
+    ) {
+        if self.abs_mass == 0.0 {
+            return;
+        }
+
+        if let Some(leaf_idx) = self.body_index {
+            if leaf_idx != body_index {
+                let leaf_sq = leaf_softening_sq(leaf_idx);
+                let accel =
+                    direct_accel(pos, &self.center_of_mass, self.total_mass, params, leaf_sq);
+                out.push(TreeTerm {
+                    body_index: Some(leaf_idx),
+                    center_of_mass: self.center_of_mass,
+                    mass: self.total_mass,
+                    size: 0.0,
+                    accel,
+                });
+            }
+            return;
+        }
+
+        let diff = self.center_of_mass - *pos;
+        let dist_sq = diff.x * diff.x + diff.y * diff.y + diff.z * diff.z + params.softening_sq;
+        let s = self.half_size * 2.0;
+
+        if s * s < params.theta * params.theta * dist_sq {
+            out.push(TreeTerm {
+                body_index: None,
+                center_of_mass: self.center_of_mass,
+                mass: self.total_mass,
+                size: s,
+                accel: direct_accel(
+                    pos,
+                    &self.center_of_mass,
+                    self.total_mass,
+                    params,
+                    params.softening_sq,
+                ),
+            });
+            return;
+        }
+
+        for child in self.children.iter().flatten() {
+            child.contributions(pos, body_index, params, leaf_softening_sq, out);
+        }
+    }
+
+    // (node count, depth) of the subtree rooted here; a lone leaf is depth 1
+    pub fn stats(&self) -> (usize, usize) {
+        let mut nodes = 1;
+        let mut depth = 0;
+        for child in self.children.iter().flatten() {
+            let (child_nodes, child_depth) = child.stats();
+            nodes += child_nodes;
+            depth = depth.max(child_depth);
+        }
+        (nodes, depth + 1)
+    }
+}
+
+// One term of a tree walk: a single body (`body_index` set, `size` zero) or
+// a far node of side `size` standing in for every body under it
+pub struct TreeTerm {
+    pub body_index: Option<usize>,
+    pub center_of_mass: Vec3,
+    pub mass: f64,
+    pub size: f64,
+    pub accel: Vec3,
+}
+
+// Force-law settings shared by every node visit in a traversal
+pub struct ForceParams {
+    pub g: f64,
+    pub softening_sq: f64,
+    pub theta: f64,
+    pub kernel: SofteningKernel,
+}
+
+fn direct_accel(
+    pos: &Vec3,
+    other_pos: &Vec3,
+    other_mass: f64,
+    params: &ForceParams,
+    softening_sq: f64,
+) -> Vec3 {
     let diff = *other_pos - *pos;
-    let dist_sq = diff.x * diff.x + diff.y * diff.y + diff.z * diff.z + softening_sq;
-    let dist = dist_sq.sqrt();
-    let force_mag = g * other_mass / dist_sq;
-    diff.scale(force_mag / dist)
+    let dist_sq = diff.x * diff.x + diff.y * diff.y + diff.z * diff.z;
+    diff.scale(params.g * other_mass * params.kernel.inv_r3(dist_sq, softening_sq))
 }
 
-pub fn build_octree(positions: &[Vec3], masses: &[f64]) -> OctreeNode {
-    // Find bounding box
-    let mut min_x = f64::MAX;
-    let mut min_y = f64::MAX;
-    let mut min_z = f64::MAX;
-    let mut max_x = f64::MIN;
-    let mut max_y = f64::MIN;
-    let mut max_z = f64::MIN;
-
-    for p in positions {
-        min_x = min_x.min(p.x);
-        min_y = min_y.min(p.y);
-        min_z = min_z.min(p.z);
-        max_x = max_x.max(p.x);
-        max_y = max_y.max(p.y);
-        max_z = max_z.max(p.z);
+// Cube the root node spans
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct TreeBounds {
+    pub center: Vec3,
+    pub half_size: f64,
+}
+
+impl TreeBounds {
+    // Smallest cube around the positions, padded by one unit
+    pub fn fit<'a>(positions: impl IntoIterator<Item = &'a Vec3>) -> Self {
+        let mut min_x = f64::MAX;
+        let mut min_y = f64::MAX;
+        let mut min_z = f64::MAX;
+        let mut max_x = f64::MIN;
+        let mut max_y = f64::MIN;
+        let mut max_z = f64::MIN;
+
+        for p in positions {
+            min_x = min_x.min(p.x);
+            min_y = min_y.min(p.y);
+            min_z = min_z.min(p.z);
+            max_x = max_x.max(p.x);
+            max_y = max_y.max(p.y);
+            max_z = max_z.max(p.z);
+        }
+
+        let cx = (min_x + max_x) * 0.5;
+        let cy = (min_y + max_y) * 0.5;
+        let cz = (min_z + max_z) * 0.5;
+        let half_size = ((max_x - min_x).max(max_y - min_y).max(max_z - min_z)) * 0.5 + 1.0;
+        Self { center: Vec3::new(cx, cy, cz), half_size }
     }
 
-    let cx = (min_x + max_x) * 0.5;
-    let cy = (min_y + max_y) * 0.5;
-    let cz = (min_z + max_z) * 0.5;
-    let half_size = ((max_x - min_x).max(max_y - min_y).max(max_z - min_z)) * 0.5 + 1.0;
+    pub fn contains(&self, p: &Vec3) -> bool {
+        let d = *p - self.center;
+        d.x.abs() <= self.half_size && d.y.abs() <= self.half_size && d.z.abs() <= self.half_size
+    }
+}
+
+pub fn build_octree(positions: &[Vec3], masses: &[f64]) -> OctreeNode {
+    build_octree_in(TreeBounds::fit(positions), positions, masses)
+}
 
-    let mut root = OctreeNode::new(Vec3::new(cx, cy, cz), half_size);
+// Bodies outside `bounds` are left out of the tree; the caller has to add
+// their pull some other way
+pub fn build_octree_in(bounds: TreeBounds, positions: &[Vec3], masses: &[f64]) -> OctreeNode {
+    let mut root = OctreeNode::new(bounds.center, bounds.half_size);
 
+    // Massless entries (test particles) can't pull on anything, so keep them
+    // out of the tree entirely
     for (i, (pos, &mass)) in positions.iter().zip(masses.iter()).enumerate() {
-        root.insert(i, pos, mass, 0);
+        if mass != 0.0 && bounds.contains(pos) {
+            root.insert(i, pos, mass, 0);
+        }
     }
 
     root
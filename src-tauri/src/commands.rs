@@ -1,14 +1,74 @@
-use crate::galaxy;
-use crate::physics::{BodyType, CelestialBody, Vec3};
-use crate::procedural;
-use crate::scenarios;
-use crate::simulation::SimulationState;
-use serde::Deserialize;
+use crate::barneshut::TreeBounds;
+use crate::galaxy::{self, DarkHalo, GalaxySpec, SpiralArms};
+use crate::physics::{self, BodyType, CelestialBody, SofteningKernel, TrailPoint, Vec3};
+use crate::pm;
+use crate::procedural::{self, EccentricityRange};
+use crate::rng::SimRng;
+use crate::scenarios::{self, ScenarioInfo};
+use crate::simulation::{
+    AccelerationBreakdown, ActiveSolver, CollisionConfig, CollisionEvent, CollisionStats,
+    ColorScheme, DtReport, FrameLod, GenerationProgress, Integrator, ManeuverNode, OverflowPolicy,
+    PrecessionReport, RepairReport, ScenarioLoadedEvent, SimulationFrame, SimulationState,
+    SnapshotInfo, SofteningMode, SolverKind, SpeedRange, StabilityReport, ThetaReport,
+    TrailFrame,
+};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
-use tauri::State;
+use tauri::{AppHandle, Emitter, Manager, State};
 
 pub type SimState = Arc<Mutex<SimulationState>>;
 
+// Why a command failed, serialized as {"kind": "body_not_found", "id": 3} and
+// so on, so the frontend can branch on `kind` instead of parsing messages
+#[derive(Debug, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum CommandError {
+    BodyNotFound { id: u32 },
+    NotASpacecraft { id: u32 },
+    UnknownScenario { name: String },
+    BodyLimit { max_bodies: usize },
+    // Rejected argument or setting; the message says which and why
+    InvalidParameter { message: String },
+    // The scene has nothing for the command to act on (no ghost, nothing
+    // collapsed, no bound bodies)
+    InvalidState { message: String },
+    // File access or (de)serialization
+    Io { message: String },
+}
+
+impl CommandError {
+    fn io(e: impl fmt::Display) -> Self {
+        CommandError::Io { message: e.to_string() }
+    }
+
+    // For the simulation's validators, which report plain strings
+    fn invalid(message: String) -> Self {
+        CommandError::InvalidParameter { message }
+    }
+
+    fn state(message: &str) -> Self {
+        CommandError::InvalidState { message: message.to_string() }
+    }
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CommandError::BodyNotFound { id } => write!(f, "Body {} not found", id),
+            CommandError::NotASpacecraft { id } => write!(f, "Body {} is not a spacecraft", id),
+            CommandError::UnknownScenario { name } => write!(f, "Unknown scenario '{}'", name),
+            CommandError::BodyLimit { max_bodies } => {
+                write!(f, "Body limit of {} reached", max_bodies)
+            }
+            CommandError::InvalidParameter { message }
+            | CommandError::InvalidState { message }
+            | CommandError::Io { message } => f.write_str(message),
+        }
+    }
+}
+
 #[derive(Deserialize)]
 pub struct BodyData {
     pub x: f64,
@@ -28,6 +88,18 @@ pub struct BodyData {
     pub body_type: BodyType,
 }
 
+#[derive(Deserialize)]
+pub struct OrbitingBodyData {
+    pub primary_id: u32,
+    pub orbit_radius: f64,
+    pub mass: f64,
+    pub radius: f64,
+    pub color: String,
+    pub name: String,
+    #[serde(default)]
+    pub inclination: f64,
+}
+
 #[derive(Deserialize)]
 pub struct BodyUpdate {
     pub mass: Option<f64>,
@@ -35,6 +107,33 @@ pub struct BodyUpdate {
     pub color: Option<String>,
     pub name: Option<String>,
     pub is_fixed: Option<bool>,
+    pub record_trail_enabled: Option<bool>,
+    // Outgassing strength; zero removes the term
+    pub nongrav: Option<f64>,
+    pub no_merge: Option<bool>,
+}
+
+#[derive(Serialize)]
+pub struct StepResult {
+    pub frame: SimulationFrame,
+    pub collisions: Vec<CollisionEvent>,
+}
+
+// One body's full state plus derived values, for inspector panels
+#[derive(Serialize)]
+pub struct BodyDetails {
+    #[serde(flatten)]
+    pub body: CelestialBody,
+    pub speed: f64,
+}
+
+// Net momentum of the scene. `drift_velocity` is how fast the barycenter
+// moves, i.e. how quickly the whole system slides off screen.
+#[derive(Serialize)]
+pub struct MomentumReport {
+    pub momentum: Vec3,
+    pub magnitude: f64,
+    pub drift_velocity: Vec3,
 }
 
 #[tauri::command]
@@ -45,31 +144,111 @@ pub fn toggle_pause(state: State<SimState>) -> bool {
 }
 
 #[tauri::command]
-pub fn set_speed(state: State<SimState>, multiplier: f64) -> f64 {
+pub fn step_once(state: State<SimState>) -> StepResult {
     let mut sim = state.lock().unwrap();
-    sim.speed_multiplier = multiplier.clamp(0.25, 8.0);
-    sim.speed_multiplier
+    let collisions = sim.step_once();
+    StepResult {
+        frame: sim.to_frame(),
+        collisions,
+    }
 }
 
 #[tauri::command]
-pub fn load_test_scenario(state: State<SimState>) {
+pub fn set_speed(state: State<SimState>, multiplier: f64) -> Result<f64, CommandError> {
     let mut sim = state.lock().unwrap();
-    scenarios::load_sun_earth(&mut sim);
+    sim.set_speed_multiplier(multiplier).map_err(CommandError::invalid)
 }
 
+// Damps every free body toward a circular orbit about its primary for the
+// next `ticks` ticks, then stops on its own. Meant for letting a freshly
+// generated disc relax before anything interesting happens.
 #[tauri::command]
-pub fn clear_simulation(state: State<SimState>) {
+pub fn set_settling(state: State<SimState>, drag: f64, ticks: u64) -> Result<(), CommandError> {
     let mut sim = state.lock().unwrap();
-    sim.clear();
+    sim.settle(drag, ticks).map_err(CommandError::invalid)
 }
 
+// Splits every dt into this many integration steps (clamped to 1..=64); the
+// speed multiplier still decides how much simulated time each tick covers
 #[tauri::command]
-pub fn add_body(state: State<SimState>, body_data: BodyData) -> u32 {
+pub fn set_substeps(state: State<SimState>, substeps: u32) -> u32 {
     let mut sim = state.lock().unwrap();
-    let id = sim.allocate_id();
-    let mass = body_data.mass.max(0.01);
+    sim.set_substeps(substeps)
+}
+
+// Rebuilds the physics thread pool with this many threads (clamped to
+// 1..=cores) and returns the count applied
+#[tauri::command]
+pub fn set_physics_threads(state: State<SimState>, threads: usize) -> Result<usize, CommandError> {
+    let mut sim = state.lock().unwrap();
+    // Threads are clamped to the cores, so only the pool itself can fail
+    sim.set_physics_threads(threads).map_err(CommandError::io)
+}
+
+#[tauri::command]
+pub fn load_test_scenario(app: AppHandle, state: State<SimState>) {
+    let loaded = {
+        let mut sim = state.lock().unwrap();
+        scenarios::load_sun_earth(&mut sim);
+        sim.scenario_loaded("sun_earth")
+    };
+    emit_scenario_loaded(&app, loaded);
+}
+
+// A load is "done" once the loader has returned and its bodies carry primed
+// accelerations; the event is emitted after the state lock is released so the
+// listener can immediately query the new system.
+fn emit_scenario_loaded(app: &AppHandle, loaded: ScenarioLoadedEvent) {
+    let _ = app.emit("scenario-loaded", loaded);
+}
+
+// Generations below this size finish too quickly for progress to matter
+const PROGRESS_MIN_BODIES: usize = 5_000;
+const PROGRESS_INTERVAL: usize = 1_000;
+
+fn progress_reporter<'a>(app: &'a AppHandle, name: &'a str) -> impl FnMut(usize, usize) + 'a {
+    move |generated, total| {
+        if total >= PROGRESS_MIN_BODIES && (generated % PROGRESS_INTERVAL == 0 || generated == total)
+        {
+            let _ = app.emit(
+                "generation-progress",
+                GenerationProgress { name: name.to_string(), generated, total },
+            );
+        }
+    }
+}
+
+// Builds a scene into a staging copy without holding the lock, so the tick
+// keeps rendering the old scene, then swaps it in under a brief lock. A
+// failed generation leaves the live scene untouched.
+fn generate_off_lock(
+    app: &AppHandle,
+    state: &SimState,
+    name: &str,
+    generate: impl FnOnce(&mut SimulationState, &mut dyn FnMut(usize, usize)) -> Result<(), String>,
+) -> Result<(), CommandError> {
+    let mut staged = state.lock().unwrap().staging_state();
+    generate(&mut staged, &mut progress_reporter(app, name)).map_err(CommandError::invalid)?;
+
+    let loaded = {
+        let mut sim = state.lock().unwrap();
+        sim.install_staged(staged);
+        sim.scenario_loaded(name)
+    };
+    emit_scenario_loaded(app, loaded);
+    Ok(())
+}
+
+#[tauri::command]
+pub fn clear_simulation(state: State<SimState>) {
+    let mut sim = state.lock().unwrap();
+    sim.clear();
+}
+
+fn body_from_data(sim: &SimulationState, id: u32, body_data: BodyData) -> CelestialBody {
+    let mass = sim.clamp_mass(body_data.mass);
     let radius = body_data.radius.max(0.5);
-    let mut body = CelestialBody::new(
+    CelestialBody::new(
         id,
         &body_data.name,
         Vec3::new(body_data.x, body_data.y, body_data.z),
@@ -78,126 +257,972 @@ pub fn add_body(state: State<SimState>, body_data: BodyData) -> u32 {
         radius,
         &body_data.color,
         body_data.is_fixed,
+    )
+    .with_type(body_data.body_type)
+}
+
+fn body_limit_error(sim: &SimulationState) -> CommandError {
+    CommandError::BodyLimit { max_bodies: sim.max_bodies }
+}
+
+pub(crate) fn add_body_from_data(
+    sim: &mut SimulationState,
+    body_data: BodyData,
+) -> Result<u32, CommandError> {
+    if sim.make_room(1, None) == 0 {
+        return Err(body_limit_error(sim));
+    }
+    let id = sim.allocate_id();
+    let body = body_from_data(sim, id, body_data);
+    sim.add_body(body);
+    Ok(id)
+}
+
+#[tauri::command]
+pub fn add_body(state: State<SimState>, body_data: BodyData) -> Result<u32, CommandError> {
+    let mut sim = state.lock().unwrap();
+    add_body_from_data(&mut sim, body_data)
+}
+
+// Adds as many of the bodies as fit under the cap (in order) and returns how
+// many were added. Accelerations are refreshed once for the whole batch.
+#[tauri::command]
+pub fn add_bodies(state: State<SimState>, bodies: Vec<BodyData>) -> usize {
+    let mut sim = state.lock().unwrap();
+    let admitted = sim.make_room(bodies.len(), None);
+    for body_data in bodies.into_iter().take(admitted) {
+        let id = sim.allocate_id();
+        let body = body_from_data(&sim, id, body_data);
+        sim.bodies.push(body);
+    }
+    sim.prime_accelerations();
+    admitted
+}
+
+// Places (or moves) the placement preview and returns its predicted path
+#[tauri::command]
+pub fn set_ghost_body(state: State<SimState>, body_data: BodyData, steps: Option<u32>) -> Vec<Vec3> {
+    let mut sim = state.lock().unwrap();
+    let ghost = body_from_data(&sim, 0, body_data);
+    sim.ghost = Some(ghost);
+    sim.predict_ghost_orbit(steps.unwrap_or(500).min(2000))
+}
+
+#[tauri::command]
+pub fn clear_ghost_body(state: State<SimState>) {
+    let mut sim = state.lock().unwrap();
+    sim.ghost = None;
+}
+
+#[tauri::command]
+pub fn commit_ghost_body(state: State<SimState>) -> Result<u32, CommandError> {
+    let mut sim = state.lock().unwrap();
+    if sim.ghost.is_none() {
+        return Err(CommandError::state("No ghost body to commit"));
+    }
+    // With a ghost to commit, the only way left to fail is the body limit
+    sim.commit_ghost().map_err(|_| body_limit_error(&sim))
+}
+
+#[tauri::command]
+pub fn add_orbiting_body(
+    state: State<SimState>,
+    body_data: OrbitingBodyData,
+) -> Result<u32, CommandError> {
+    let mut sim = state.lock().unwrap();
+    let (primary_pos, primary_vel, primary_mass) = match sim.find_body(body_data.primary_id) {
+        Some(p) => (p.position, p.velocity, p.source_mass()),
+        None => return Err(CommandError::BodyNotFound { id: body_data.primary_id }),
+    };
+    if sim.make_room(1, Some(body_data.primary_id)) == 0 {
+        return Err(body_limit_error(&sim));
+    }
+    let mass = sim.clamp_mass(body_data.mass);
+    let radius = body_data.radius.max(0.5);
+    let orbit_radius = body_data.orbit_radius.max(radius);
+    let inclination = body_data.inclination;
+
+    // Circular two-body speed, tilted out of the xy-plane by the inclination
+    // and carried along with the primary's own motion. A pair whose masses
+    // cancel or repel has no circular orbit, so the body starts at rest
+    // relative to the primary.
+    let v = (sim.g * (primary_mass + mass) / orbit_radius).max(0.0).sqrt();
+    let position = primary_pos + Vec3::new(orbit_radius, 0.0, 0.0);
+    let velocity = primary_vel + Vec3::new(0.0, v * inclination.cos(), v * inclination.sin());
+
+    let id = sim.allocate_id();
+    let body = CelestialBody::new(
+        id,
+        &body_data.name,
+        position,
+        velocity,
+        mass,
+        radius,
+        &body_data.color,
+        false,
     );
-    body.body_type = body_data.body_type;
     sim.add_body(body);
-    id
+    Ok(id)
 }
 
+// Ring, accretion disc or debris field around an existing body. Returns the
+// ids of the particles that fit under the body cap.
 #[tauri::command]
-pub fn remove_body(state: State<SimState>, id: u32) {
+pub fn add_disc(
+    state: State<SimState>,
+    center_body_id: u32,
+    inner_r: f64,
+    outer_r: f64,
+    count: usize,
+    thickness: f64,
+    test_particles: bool,
+) -> Result<Vec<u32>, CommandError> {
     let mut sim = state.lock().unwrap();
+    if sim.find_body(center_body_id).is_none() {
+        return Err(CommandError::BodyNotFound { id: center_body_id });
+    }
+    let ids = sim
+        .add_disc(center_body_id, inner_r, outer_r, count, thickness, test_particles)
+        .map_err(CommandError::invalid)?;
+    if ids.is_empty() && count > 0 {
+        return Err(body_limit_error(&sim));
+    }
+    Ok(ids)
+}
+
+#[tauri::command]
+pub fn remove_body(state: State<SimState>, id: u32) -> Result<(), CommandError> {
+    let mut sim = state.lock().unwrap();
+    if sim.find_body(id).is_none() {
+        return Err(CommandError::BodyNotFound { id });
+    }
     sim.remove_body(id);
+    Ok(())
 }
 
+// Swaps the whole scene for a single body with its total mass and momentum.
+// `uncollapse_system` brings the original bodies back.
 #[tauri::command]
-pub fn update_body(state: State<SimState>, id: u32, fields: BodyUpdate) {
+pub fn collapse_system(state: State<SimState>) -> Result<u32, CommandError> {
     let mut sim = state.lock().unwrap();
-    if let Some(body) = sim.find_body_mut(id) {
-        if let Some(mass) = fields.mass {
-            body.mass = mass.max(0.01);
-        }
-        if let Some(radius) = fields.radius {
-            body.radius = radius.max(0.5);
-        }
-        if let Some(color) = fields.color {
-            body.color = color;
-        }
-        if let Some(name) = fields.name {
-            body.name = name;
-        }
-        if let Some(is_fixed) = fields.is_fixed {
-            body.is_fixed = is_fixed;
+    // Its one failure is a scene with no positive mass to collapse
+    sim.collapse().map_err(|e| CommandError::state(&e))
+}
+
+#[tauri::command]
+pub fn uncollapse_system(state: State<SimState>) -> Result<(), CommandError> {
+    let mut sim = state.lock().unwrap();
+    if !sim.uncollapse() {
+        return Err(CommandError::state("No collapsed system to restore"));
+    }
+    Ok(())
+}
+
+// None removes the body from whatever group it was in
+#[tauri::command]
+pub fn set_body_group(
+    state: State<SimState>,
+    id: u32,
+    group: Option<String>,
+) -> Result<(), CommandError> {
+    let mut sim = state.lock().unwrap();
+    let body = sim.find_body_mut(id).ok_or(CommandError::BodyNotFound { id })?;
+    body.group = group.filter(|g| !g.is_empty());
+    Ok(())
+}
+
+#[tauri::command]
+pub fn remove_group(state: State<SimState>, name: String) -> usize {
+    let mut sim = state.lock().unwrap();
+    sim.remove_group(&name)
+}
+
+#[tauri::command]
+pub fn translate_group(state: State<SimState>, name: String, offset: Vec3) -> usize {
+    let mut sim = state.lock().unwrap();
+    sim.translate_group(&name, offset)
+}
+
+#[tauri::command]
+pub fn recolor_group(state: State<SimState>, name: String, color: String) -> usize {
+    let mut sim = state.lock().unwrap();
+    sim.recolor_group(&name, &color)
+}
+
+// `acceleration` is as of the last step or edit; call `reprime` first if
+// bodies were changed while paused
+#[tauri::command]
+pub fn get_body(state: State<SimState>, id: u32) -> Option<BodyDetails> {
+    let sim = state.lock().unwrap();
+    sim.find_body(id).map(|body| BodyDetails {
+        speed: body.velocity.magnitude(),
+        body: body.clone(),
+    })
+}
+
+#[tauri::command]
+pub fn update_body(
+    state: State<SimState>,
+    id: u32,
+    fields: BodyUpdate,
+) -> Result<(), CommandError> {
+    let mut sim = state.lock().unwrap();
+    let mass = fields.mass.map(|m| sim.clamp_mass(m));
+    let body = sim.find_body_mut(id).ok_or(CommandError::BodyNotFound { id })?;
+    if let Some(mass) = mass {
+        body.mass = mass;
+    }
+    if let Some(radius) = fields.radius {
+        body.radius = radius.max(0.5);
+    }
+    if let Some(color) = fields.color {
+        body.color = color;
+    }
+    if let Some(name) = fields.name {
+        body.name = name;
+    }
+    if let Some(is_fixed) = fields.is_fixed {
+        body.is_fixed = is_fixed;
+    }
+    if let Some(enabled) = fields.record_trail_enabled {
+        body.record_trail_enabled = enabled;
+        // Drop the existing history so disabling actually frees memory
+        if !enabled {
+            body.trail.clear();
         }
     }
+    if let Some(strength) = fields.nongrav {
+        body.nongrav = (strength != 0.0).then_some(strength);
+    }
+    if let Some(no_merge) = fields.no_merge {
+        body.no_merge = no_merge;
+    }
+    Ok(())
+}
+
+// Recomputes every body's cached acceleration from the current state.
+// Edits like `update_body` leave it stale until the next step, so call this
+// after a batch of edits made while paused, before querying predictions,
+// Hill radii or acceleration vectors.
+#[tauri::command]
+pub fn reprime(state: State<SimState>) {
+    let mut sim = state.lock().unwrap();
+    sim.prime_accelerations();
+}
+
+// Returns a warning when a velocity-dependent force is on and the reversed
+// run won't retrace its path
+#[tauri::command]
+pub fn reverse(state: State<SimState>) -> Option<String> {
+    let mut sim = state.lock().unwrap();
+    sim.reverse()
+}
+
+#[tauri::command]
+pub fn update_body_velocity(
+    state: State<SimState>,
+    id: u32,
+    vx: f64,
+    vy: f64,
+    vz: Option<f64>,
+) -> Result<(), CommandError> {
+    let mut sim = state.lock().unwrap();
+    let body = sim.find_body_mut(id).ok_or(CommandError::BodyNotFound { id })?;
+    body.velocity = Vec3::new(vx, vy, vz.unwrap_or(0.0));
+    Ok(())
+}
+
+// Adds `impulse / mass` to the current velocity, unlike
+// `update_body_velocity` which overwrites it. Fixed bodies don't move.
+#[tauri::command]
+pub fn apply_impulse(state: State<SimState>, id: u32, impulse: Vec3) -> Result<(), CommandError> {
+    let mut sim = state.lock().unwrap();
+    let body = sim.find_body_mut(id).ok_or(CommandError::BodyNotFound { id })?;
+    if body.is_fixed || body.mass == 0.0 {
+        return Err(CommandError::invalid(format!("Body {} is fixed or massless", id)));
+    }
+    body.velocity += impulse.scale(1.0 / body.mass);
+    Ok(())
+}
+
+#[tauri::command]
+pub fn set_spin(
+    state: State<SimState>,
+    id: u32,
+    wx: f64,
+    wy: f64,
+    wz: f64,
+) -> Result<(), CommandError> {
+    let mut sim = state.lock().unwrap();
+    let body = sim.find_body_mut(id).ok_or(CommandError::BodyNotFound { id })?;
+    body.angular_velocity = Vec3::new(wx, wy, wz);
+    Ok(())
 }
 
+// Exhaust plumes behind thrusting spacecraft; `lifetime` is in ticks and is
+// kept when omitted
 #[tauri::command]
-pub fn update_body_velocity(state: State<SimState>, id: u32, vx: f64, vy: f64, vz: Option<f64>) {
+pub fn set_exhaust(state: State<SimState>, enabled: bool, lifetime: Option<u64>) {
     let mut sim = state.lock().unwrap();
-    if let Some(body) = sim.find_body_mut(id) {
-        body.velocity = Vec3::new(vx, vy, vz.unwrap_or(0.0));
+    sim.spawn_exhaust = enabled;
+    if let Some(lifetime) = lifetime {
+        sim.exhaust_lifetime = lifetime.max(1);
     }
 }
 
 #[tauri::command]
-pub fn set_spacecraft_thrust(state: State<SimState>, id: u32, tx: f64, ty: f64, tz: f64) {
+pub fn set_spacecraft_thrust(
+    state: State<SimState>,
+    id: u32,
+    tx: f64,
+    ty: f64,
+    tz: f64,
+) -> Result<(), CommandError> {
     let mut sim = state.lock().unwrap();
-    if let Some(body) = sim.find_body_mut(id) {
-        if body.body_type == BodyType::Spacecraft {
-            body.thrust = Vec3::new(tx, ty, tz);
+    let body = sim.find_body_mut(id).ok_or(CommandError::BodyNotFound { id })?;
+    if body.body_type != BodyType::Spacecraft {
+        return Err(CommandError::NotASpacecraft { id });
+    }
+    body.thrust = Vec3::new(tx, ty, tz);
+    Ok(())
+}
+
+fn user_scenario_dir(app: &AppHandle) -> Result<PathBuf, CommandError> {
+    let data_dir = app.path().app_data_dir().map_err(CommandError::io)?;
+    Ok(data_dir.join("scenarios"))
+}
+
+// Built-in presets, in picker order
+#[tauri::command]
+pub fn list_scenarios() -> Vec<ScenarioInfo> {
+    scenarios::BUILTIN_SCENARIOS.to_vec()
+}
+
+// Saved scenarios are looked up before the built-ins. Saving refuses built-in
+// names, so a preset is only shadowed by a file placed there by hand.
+#[tauri::command]
+pub fn load_scenario(
+    app: AppHandle,
+    state: State<SimState>,
+    name: String,
+) -> Result<(), CommandError> {
+    // Without a data directory there are simply no user scenarios; the
+    // built-ins still load
+    let loaded = match user_scenario_dir(&app) {
+        Ok(dir) => {
+            let mut sim = state.lock().unwrap();
+            if scenarios::load_user(&mut sim, &dir, &name).map_err(CommandError::io)? {
+                Some(sim.scenario_loaded(&name))
+            } else {
+                None
+            }
         }
+        Err(_) => None,
+    };
+    if let Some(loaded) = loaded {
+        emit_scenario_loaded(&app, loaded);
+        return Ok(());
     }
+
+    if name == "galaxy_collision" {
+        return generate_off_lock(&app, &state, &name, |sim, progress| {
+            galaxy::generate_collision(sim, 300, false, None, None, progress);
+            Ok(())
+        });
+    }
+
+    let loaded = {
+        let mut sim = state.lock().unwrap();
+        // Unknown names leave the state untouched
+        if !scenarios::load_by_name(&mut sim, &name) {
+            return Err(CommandError::UnknownScenario { name });
+        }
+        sim.scenario_loaded(&name)
+    };
+    emit_scenario_loaded(&app, loaded);
+    Ok(())
+}
+
+// Writes the current bodies and settings as a scenario `load_scenario` can
+// find by name. Returns the file written.
+#[tauri::command]
+pub fn save_named_scenario(
+    app: AppHandle,
+    state: State<SimState>,
+    name: String,
+) -> Result<String, CommandError> {
+    let dir = user_scenario_dir(&app)?;
+    let sim = state.lock().unwrap();
+    scenarios::check_save_name(&name).map_err(CommandError::invalid)?;
+    let path = scenarios::save_user(&sim, &dir, &name).map_err(CommandError::io)?;
+    Ok(path.display().to_string())
 }
 
+// Adds a preset to the current scene instead of replacing it
 #[tauri::command]
-pub fn load_scenario(state: State<SimState>, name: String) {
+pub fn append_scenario(
+    state: State<SimState>,
+    name: String,
+    offset: Option<Vec3>,
+) -> Result<Vec<u32>, CommandError> {
     let mut sim = state.lock().unwrap();
-    match name.as_str() {
-        "sun_earth" => scenarios::load_sun_earth(&mut sim),
-        "inner_solar" => scenarios::load_inner_solar(&mut sim),
-        "outer_solar" => scenarios::load_outer_solar(&mut sim),
-        "full_solar" => scenarios::load_full_solar(&mut sim),
-        "binary_star" => scenarios::load_binary_star(&mut sim),
-        "figure_eight" => scenarios::load_figure_eight(&mut sim),
-        "inclined_solar" => scenarios::load_inclined_solar(&mut sim),
-        "asteroid_belt" => scenarios::load_solar_with_belt(&mut sim),
-        "galaxy_collision" => galaxy::generate_collision(&mut sim, 300),
-        _ => {}
-    }
+    scenarios::append_by_name(&mut sim, &name, offset.unwrap_or_default())
+        .ok_or(CommandError::UnknownScenario { name })
 }
 
 #[tauri::command]
 pub fn generate_system(
+    app: AppHandle,
     state: State<SimState>,
     star_mass: f64,
     planet_count: u32,
     min_spacing: f64,
     max_radius: f64,
-) {
+    eccentricity: Option<EccentricityRange>,
+) -> Result<(), CommandError> {
+    let eccentricity = eccentricity.unwrap_or_default();
+    // Checked here too so a bad range fails before the scene is cleared
+    eccentricity.validate().map_err(CommandError::invalid)?;
+    generate_off_lock(&app, &state, "procedural", |sim, progress| {
+        procedural::generate_system(
+            sim,
+            star_mass,
+            planet_count,
+            min_spacing,
+            max_radius,
+            eccentricity,
+            progress,
+        )
+    })
+}
+
+#[tauri::command]
+pub fn load_galaxy_collision(
+    app: AppHandle,
+    state: State<SimState>,
+    particles_per_galaxy: Option<u32>,
+    retrograde: Option<bool>,
+    spiral_arms: Option<SpiralArms>,
+    halo: Option<DarkHalo>,
+) -> Result<(), CommandError> {
+    generate_off_lock(&app, &state, "galaxy_collision", |sim, progress| {
+        let particles = particles_per_galaxy.unwrap_or(300);
+        let retrograde = retrograde.unwrap_or(false);
+        galaxy::generate_collision(sim, particles, retrograde, spiral_arms, halo, progress);
+        Ok(())
+    })
+}
+
+// Any number of galaxies, each with its own position, drift, core mass and
+// particle count. The body cap is shared evenly between them.
+#[tauri::command]
+pub fn load_multi_galaxy_collision(
+    app: AppHandle,
+    state: State<SimState>,
+    galaxies: Vec<GalaxySpec>,
+    spiral_arms: Option<SpiralArms>,
+    halo: Option<DarkHalo>,
+) -> Result<(), CommandError> {
+    if galaxies.is_empty() {
+        return Err(CommandError::invalid("at least one galaxy is required".to_string()));
+    }
+    generate_off_lock(&app, &state, "multi_galaxy_collision", |sim, progress| {
+        galaxy::generate_multi_collision(sim, &galaxies, spiral_arms, halo, progress);
+        Ok(())
+    })
+}
+
+#[tauri::command]
+pub fn auto_color(state: State<SimState>, scheme: ColorScheme) {
+    let mut sim = state.lock().unwrap();
+    sim.auto_color(scheme);
+}
+
+#[tauri::command]
+pub fn rotate_system(state: State<SimState>, axis: Vec3, angle_degrees: f64) {
+    let mut sim = state.lock().unwrap();
+    sim.rotate_system(axis, angle_degrees.to_radians());
+}
+
+// Returns the dt actually applied plus a warning when the request would
+// under-resolve the fastest orbit. `auto_reduce` defaults to on.
+#[tauri::command]
+pub fn set_dt(
+    state: State<SimState>,
+    dt: f64,
+    auto_reduce: Option<bool>,
+) -> Result<DtReport, CommandError> {
+    let mut sim = state.lock().unwrap();
+    sim.set_dt(dt, auto_reduce.unwrap_or(true)).map_err(CommandError::invalid)
+}
+
+#[tauri::command]
+pub fn set_theta(state: State<SimState>, theta: f64) -> Result<ThetaReport, CommandError> {
+    let mut sim = state.lock().unwrap();
+    sim.set_theta(theta).map_err(CommandError::invalid)
+}
+
+#[tauri::command]
+pub fn recenter_tree(state: State<SimState>) -> Result<TreeBounds, CommandError> {
+    let mut sim = state.lock().unwrap();
+    sim.recenter_tree()
+        .ok_or_else(|| CommandError::state("No bound massive bodies to fit the tree around"))
+}
+
+#[tauri::command]
+pub fn release_tree_bounds(state: State<SimState>) {
+    let mut sim = state.lock().unwrap();
+    sim.tree_bounds = None;
+}
+
+#[tauri::command]
+pub fn set_gravity(state: State<SimState>, g: f64) -> Result<(), CommandError> {
+    let mut sim = state.lock().unwrap();
+    sim.set_gravity(g).map_err(CommandError::invalid)
+}
+
+#[tauri::command]
+pub fn set_softening(state: State<SimState>, softening: f64) -> Result<(), CommandError> {
+    let mut sim = state.lock().unwrap();
+    sim.set_softening(softening).map_err(CommandError::invalid)
+}
+
+// Dry-run for set_gravity/set_softening: steps a copy of the scene with the
+// candidate values and reports whether energy stayed bounded
+#[tauri::command]
+pub fn validate_stability(
+    state: State<SimState>,
+    g: f64,
+    softening: f64,
+    steps: Option<u32>,
+) -> Result<StabilityReport, CommandError> {
+    let sim = state.lock().unwrap();
+    sim.validate_stability(g, softening, steps.unwrap_or(300).min(2000))
+        .map_err(CommandError::invalid)
+}
+
+#[tauri::command]
+pub fn set_integrator(state: State<SimState>, integrator: Integrator) {
+    let mut sim = state.lock().unwrap();
+    sim.integrator = integrator;
+}
+
+// Centers emitted frames on a body (None returns to the world frame).
+// With `relative_velocity` the focus body's velocity is subtracted as well.
+#[tauri::command]
+pub fn set_focus_body(
+    state: State<SimState>,
+    id: Option<u32>,
+    relative_velocity: Option<bool>,
+) -> Result<(), CommandError> {
+    let mut sim = state.lock().unwrap();
+    if let Some(id) = id.filter(|id| sim.find_body(*id).is_none()) {
+        return Err(CommandError::BodyNotFound { id });
+    }
+    sim.set_focus(id, relative_velocity.unwrap_or(false));
+    Ok(())
+}
+
+// Switching frames clears existing trails: points recorded in one can't be
+// carried into the other without the focus body's past positions
+#[tauri::command]
+pub fn set_trail_frame(state: State<SimState>, frame: TrailFrame) {
+    let mut sim = state.lock().unwrap();
+    sim.set_trail_frame(frame);
+}
+
+#[tauri::command]
+pub fn set_softening_mode(state: State<SimState>, mode: SofteningMode) {
+    let mut sim = state.lock().unwrap();
+    sim.set_softening_mode(mode);
+}
+
+#[tauri::command]
+pub fn set_softening_kernel(state: State<SimState>, kernel: SofteningKernel) {
+    let mut sim = state.lock().unwrap();
+    sim.softening_kernel = kernel;
+    sim.prime_accelerations();
+}
+
+// Applies to direct body-body terms (brute force and tree leaves); the GPU and
+// mesh paths keep the plain softening
+#[tauri::command]
+pub fn set_smooth_accretion(state: State<SimState>, enabled: bool) {
+    let mut sim = state.lock().unwrap();
+    sim.smooth_accretion = enabled;
+    sim.prime_accelerations();
+}
+
+// Non-inverse-square laws are a teaching toy: they run on direct summation
+// because the tree's center-of-mass approximation assumes 1/r^2
+#[tauri::command]
+pub fn set_force_exponent(state: State<SimState>, exponent: f64) -> Result<(), CommandError> {
+    let mut sim = state.lock().unwrap();
+    sim.set_force_exponent(exponent).map_err(CommandError::invalid)
+}
+
+#[tauri::command]
+pub fn set_relativistic(
+    state: State<SimState>,
+    enabled: bool,
+    light_speed: Option<f64>,
+) -> Result<(), CommandError> {
+    let mut sim = state.lock().unwrap();
+    sim.set_relativistic(enabled, light_speed).map_err(CommandError::invalid)
+}
+
+// The cap applies to future additions; bodies already over it are kept
+#[tauri::command]
+pub fn set_max_bodies(
+    state: State<SimState>,
+    max_bodies: usize,
+    policy: Option<OverflowPolicy>,
+) -> Result<usize, CommandError> {
+    let mut sim = state.lock().unwrap();
+    sim.set_max_bodies(max_bodies, policy).map_err(CommandError::invalid)
+}
+
+// Toy mode only: switching it off leaves existing negative masses in place
+// but clamps any further edits back to positive
+#[tauri::command]
+pub fn set_allow_negative_mass(state: State<SimState>, allowed: bool) {
+    let mut sim = state.lock().unwrap();
+    sim.allow_negative_mass = allowed;
+}
+
+#[tauri::command]
+pub fn set_nan_guard(state: State<SimState>, enabled: bool) {
+    let mut sim = state.lock().unwrap();
+    sim.nan_guard = enabled;
+}
+
+// `grid` is the particle-mesh resolution per side, clamped to what memory
+// allows; it is kept when switching back to `Auto`
+#[tauri::command]
+pub fn set_solver(state: State<SimState>, kind: SolverKind, grid: Option<usize>) -> usize {
+    let mut sim = state.lock().unwrap();
+    sim.solver = kind;
+    if let Some(grid) = grid {
+        sim.pm_grid = grid.clamp(pm::MIN_GRID, pm::MAX_GRID);
+    }
+    sim.pm_grid
+}
+
+#[tauri::command]
+pub fn set_gpu_enabled(state: State<SimState>, enabled: bool) {
+    let mut sim = state.lock().unwrap();
+    sim.use_gpu = enabled;
+}
+
+#[tauri::command]
+pub fn set_gpu_precise(state: State<SimState>, enabled: bool) {
+    let mut sim = state.lock().unwrap();
+    sim.gpu_precise = enabled;
+}
+
+#[tauri::command]
+pub fn set_accel_thresholds(
+    state: State<SimState>,
+    brute_max: usize,
+    gpu_min: usize,
+) -> Result<(), CommandError> {
+    let mut sim = state.lock().unwrap();
+    sim.set_accel_thresholds(brute_max, gpu_min).map_err(CommandError::invalid)
+}
+
+#[tauri::command]
+pub fn set_collision_config(
+    state: State<SimState>,
+    config: CollisionConfig,
+) -> Result<(), CommandError> {
+    config.validate().map_err(CommandError::invalid)?;
+    let mut sim = state.lock().unwrap();
+    sim.collision = config;
+    Ok(())
+}
+
+// `box_size` None turns periodic boundaries off
+#[tauri::command]
+pub fn set_periodic(state: State<SimState>, box_size: Option<f64>) -> Result<(), CommandError> {
     let mut sim = state.lock().unwrap();
-    procedural::generate_system(&mut sim, star_mass, planet_count, min_spacing, max_radius);
+    sim.set_periodic(box_size).map_err(CommandError::invalid)
 }
 
 #[tauri::command]
-pub fn load_galaxy_collision(state: State<SimState>, particles_per_galaxy: Option<u32>) {
+pub fn set_orbit_info(state: State<SimState>, enabled: bool) {
     let mut sim = state.lock().unwrap();
-    galaxy::generate_collision(&mut sim, particles_per_galaxy.unwrap_or(300));
+    sim.orbit_info = enabled;
+}
+
+#[tauri::command]
+pub fn set_frame_lod(state: State<SimState>, lod: Option<FrameLod>) -> Result<(), CommandError> {
+    let mut sim = state.lock().unwrap();
+    sim.set_frame_lod(lod).map_err(CommandError::invalid)
+}
+
+#[tauri::command]
+pub fn set_period_trails(state: State<SimState>, enabled: bool) {
+    let mut sim = state.lock().unwrap();
+    sim.period_trails = enabled;
+    if enabled {
+        sim.fit_trails_to_periods();
+    } else {
+        sim.reset_trail_lengths();
+    }
+}
+
+// Pass None to remove the cap
+#[tauri::command]
+pub fn set_max_speed(state: State<SimState>, max_speed: Option<f64>) -> Result<(), CommandError> {
+    let mut sim = state.lock().unwrap();
+    sim.set_max_speed(max_speed).map_err(CommandError::invalid)
+}
+
+// Reseeds the random source so the next generated scene (and everything
+// random after it) can be reproduced
+#[tauri::command]
+pub fn set_rng_seed(state: State<SimState>, seed: u64) {
+    let mut sim = state.lock().unwrap();
+    sim.rng = SimRng::from_seed(seed);
+}
+
+#[tauri::command]
+pub fn get_collision_stats(state: State<SimState>) -> CollisionStats {
+    let sim = state.lock().unwrap();
+    sim.collision_stats.clone()
+}
+
+// Synchronous counterpart to the per-frame telemetry path
+#[tauri::command]
+pub fn get_active_solver(state: State<SimState>) -> ActiveSolver {
+    let sim = state.lock().unwrap();
+    sim.active_solver()
+}
+
+// Running min/max trail speed for normalizing the trail color gradient
+#[tauri::command]
+pub fn get_speed_range(state: State<SimState>) -> Option<SpeedRange> {
+    let sim = state.lock().unwrap();
+    sim.speed_range
+}
+
+#[tauri::command]
+pub fn reset_speed_range(state: State<SimState>) -> Option<SpeedRange> {
+    let mut sim = state.lock().unwrap();
+    sim.reset_speed_range()
+}
+
+// Starts (or stops) recording the body's apsides
+#[tauri::command]
+pub fn set_precession_tracking(
+    state: State<SimState>,
+    body_id: u32,
+    enabled: bool,
+) -> Result<(), CommandError> {
+    let mut sim = state.lock().unwrap();
+    if !sim.set_precession_tracking(body_id, enabled) {
+        return Err(CommandError::BodyNotFound { id: body_id });
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_precession_rate(state: State<SimState>, body_id: u32) -> Option<PrecessionReport> {
+    let sim = state.lock().unwrap();
+    sim.precession_rate(body_id)
 }
 
+// The `top_k` (default 10) largest gravity terms on a body, for finding out
+// what is throwing it around
 #[tauri::command]
-pub fn set_theta(state: State<SimState>, theta: f64) {
+pub fn explain_acceleration(
+    state: State<SimState>,
+    body_id: u32,
+    top_k: Option<usize>,
+) -> Option<AccelerationBreakdown> {
+    let sim = state.lock().unwrap();
+    sim.explain_acceleration(body_id, top_k.unwrap_or(10))
+}
+
+#[tauri::command]
+pub fn get_hill_radius(state: State<SimState>, body_id: u32, primary_id: u32) -> Option<f64> {
+    let sim = state.lock().unwrap();
+    sim.hill_radius(body_id, primary_id)
+}
+
+#[tauri::command]
+pub fn get_momentum(state: State<SimState>) -> MomentumReport {
+    let sim = state.lock().unwrap();
+    let momentum = sim.total_momentum();
+    let total_mass: f64 = sim.bodies.iter().map(|b| b.mass).sum();
+    MomentumReport {
+        momentum,
+        magnitude: momentum.magnitude(),
+        drift_velocity: if total_mass != 0.0 {
+            momentum.scale(1.0 / total_mass)
+        } else {
+            Vec3::zero()
+        },
+    }
+}
+
+#[tauri::command]
+pub fn get_dominant_body(state: State<SimState>, x: f64, y: f64, z: f64) -> Option<u32> {
+    let sim = state.lock().unwrap();
+    sim.dominant_body_at(&Vec3::new(x, y, z))
+}
+
+#[tauri::command]
+pub fn predict_orbit(
+    state: State<SimState>,
+    body_id: u32,
+    steps: u32,
+    include_thrust: Option<bool>,
+    maneuvers: Option<Vec<ManeuverNode>>,
+) -> Vec<Vec3> {
+    let sim = state.lock().unwrap();
+    let maneuvers = maneuvers.unwrap_or_default();
+    sim.predict_orbit(body_id, steps.min(2000), include_thrust.unwrap_or(false), &maneuvers)
+}
+
+// Where a body would have gone from a past snapshot, to compare against
+// where it actually went
+#[tauri::command]
+pub fn predict_orbit_from(
+    state: State<SimState>,
+    tick: u64,
+    body_id: u32,
+    steps: u32,
+    include_thrust: Option<bool>,
+) -> Result<Vec<Vec3>, CommandError> {
+    let sim = state.lock().unwrap();
+    let steps = steps.min(2000);
+    let include_thrust = include_thrust.unwrap_or(false);
+    match sim.predict_orbit_from(tick, body_id, steps, include_thrust) {
+        Some(path) => Ok(path),
+        // Never recorded, or already dropped from the history
+        None if !sim.snapshots().iter().any(|snap| snap.tick == tick) => {
+            Err(CommandError::invalid(format!("No snapshot at tick {}", tick)))
+        }
+        None => Err(CommandError::BodyNotFound { id: body_id }),
+    }
+}
+
+#[tauri::command]
+pub fn set_snapshot_interval(state: State<SimState>, interval: u64) {
     let mut sim = state.lock().unwrap();
-    sim.theta = theta.clamp(0.0, 2.0);
+    sim.set_snapshot_interval(interval);
 }
 
 #[tauri::command]
-pub fn predict_orbit(state: State<SimState>, body_id: u32, steps: u32) -> Vec<Vec3> {
+pub fn get_snapshots(state: State<SimState>) -> Vec<SnapshotInfo> {
     let sim = state.lock().unwrap();
-    sim.predict_orbit(body_id, steps.min(2000))
+    sim.snapshots()
 }
 
 #[tauri::command]
-pub fn export_state(state: State<SimState>) -> Result<String, String> {
+pub fn export_state(state: State<SimState>) -> Result<String, CommandError> {
     let sim = state.lock().unwrap();
-    serde_json::to_string_pretty(&*sim).map_err(|e| e.to_string())
+    serde_json::to_string_pretty(&*sim).map_err(CommandError::io)
+}
+
+// Opaque to the simulation; stored so `export_state` carries it and
+// `import_state` brings it back. Clearing or loading a scene resets it to null.
+#[tauri::command]
+pub fn set_view_state(state: State<SimState>, view: serde_json::Value) {
+    let mut sim = state.lock().unwrap();
+    sim.view = view;
+}
+
+#[tauri::command]
+pub fn get_view_state(state: State<SimState>) -> serde_json::Value {
+    let sim = state.lock().unwrap();
+    sim.view.clone()
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TrailFormat {
+    #[default]
+    Json,
+    Csv,
 }
 
+#[derive(Serialize)]
+struct TrailExport<'a> {
+    id: u32,
+    name: &'a str,
+    points: Vec<TrailPoint>,
+}
+
+// Trails of every body that has one. `every_nth` thins them first, then
+// `tolerance` (in world units) applies Douglas-Peucker simplification.
 #[tauri::command]
-pub fn import_state(state: State<SimState>, json: String) -> Result<(), String> {
-    let mut new_state: SimulationState =
-        serde_json::from_str(&json).map_err(|e| e.to_string())?;
+pub fn export_trails(
+    state: State<SimState>,
+    format: Option<TrailFormat>,
+    every_nth: Option<usize>,
+    tolerance: Option<f64>,
+) -> Result<String, CommandError> {
+    let sim = state.lock().unwrap();
+    let trails: Vec<TrailExport> = sim
+        .bodies
+        .iter()
+        .filter(|b| !b.trail.is_empty())
+        .map(|b| {
+            let raw: Vec<TrailPoint> = b.trail.iter().copied().collect();
+            let mut points = physics::decimate_trail(&raw, every_nth.unwrap_or(1));
+            if let Some(tolerance) = tolerance {
+                points = physics::simplify_trail(&points, tolerance);
+            }
+            TrailExport { id: b.id, name: &b.name, points }
+        })
+        .collect();
 
-    // Ensure next_id won't collide with existing body IDs
-    let max_id = new_state.bodies.iter().map(|b| b.id).max().unwrap_or(0);
-    if new_state.next_id <= max_id {
-        new_state.next_id = max_id + 1;
+    match format.unwrap_or_default() {
+        TrailFormat::Json => serde_json::to_string_pretty(&trails).map_err(CommandError::io),
+        TrailFormat::Csv => {
+            let mut csv = String::from("body_id,name,time,x,y,z,speed,accel\n");
+            for trail in &trails {
+                // Quote names so commas in them can't shift columns
+                let name = trail.name.replace('"', "\"\"");
+                for p in &trail.points {
+                    csv.push_str(&format!(
+                        "{},\"{}\",{},{},{},{},{},{}\n",
+                        trail.id, name, p.time, p.x, p.y, p.z, p.speed, p.accel
+                    ));
+                }
+            }
+            Ok(csv)
+        }
     }
+}
+
+#[tauri::command]
+pub fn import_state(
+    app: AppHandle,
+    state: State<SimState>,
+    json: String,
+) -> Result<RepairReport, CommandError> {
+    let mut new_state: SimulationState = serde_json::from_str(&json).map_err(CommandError::io)?;
+    new_state.check_settings().map_err(CommandError::invalid)?;
+
+    // Ensure ids are unique and next_id won't collide with them
+    let report = new_state.validate_and_repair();
+
+    // Stats describe the session that produced the file, not this one
+    new_state.collision_stats = CollisionStats::default();
 
     let mut sim = state.lock().unwrap();
-    // Preserve GPU reference (lost during deserialization due to #[serde(skip)])
+    // Preserve GPU and thread pool (lost during deserialization due to #[serde(skip)])
     new_state.gpu = sim.gpu.clone();
+    new_state.pool = sim.pool.clone();
+    new_state.step_hook = sim.step_hook.take();
     new_state.prime_accelerations();
     *sim = new_state;
-    Ok(())
+    let loaded = sim.scenario_loaded("import");
+    drop(sim);
+
+    emit_scenario_loaded(&app, loaded);
+    Ok(report)
 }
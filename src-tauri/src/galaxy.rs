@@ -1,67 +1,226 @@
-use crate::physics::{CelestialBody, Vec3};
-use crate::simulation::SimulationState;
+use crate::physics::{BodyType, CelestialBody, Vec3};
+use crate::simulation::{Integrator, SimulationState};
 use rand::Rng;
+use serde::Deserialize;
 
+// Logarithmic spiral density wave imposed on a disc: `count` arms (2 or 4
+// look right) wound at `pitch_degrees` (10-30 spans tight to open spirals)
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct SpiralArms {
+    pub count: u32,
+    pub pitch_degrees: f64,
+}
+
+// Dark-matter halo around each core: `particles` equal-mass bodies sharing
+// `mass`, spread uniformly through a sphere of HALO_RADIUS. `visible` is read
+// by the frontend; hidden halo particles still pull on everything.
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct DarkHalo {
+    pub mass: f64,
+    #[serde(default = "default_halo_particles")]
+    pub particles: u32,
+    #[serde(default)]
+    pub visible: bool,
+}
+
+fn default_halo_particles() -> u32 {
+    100
+}
+
+// Arm-to-interarm contrast; below 1 so some particles stay between the arms
+const ARM_CONTRAST: f64 = 0.8;
+
+// Twice the disc's outer edge, so the whole disc sits inside its halo
+const HALO_RADIUS: f64 = 600.0;
+
+// One galaxy of a multi-way collision: a core of `core_mass` at `center`
+// with a disc of `particles` stars, all drifting at `bulk_velocity`
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct GalaxySpec {
+    pub center: Vec3,
+    #[serde(default)]
+    pub bulk_velocity: Vec3,
+    pub core_mass: f64,
+    pub particles: u32,
+    // Defaults to scaling with the cube root of the core mass
+    #[serde(default)]
+    pub core_radius: Option<f64>,
+    // Disc rotation seen from +z; counter-clockwise unless set
+    #[serde(default)]
+    pub clockwise: bool,
+}
+
+// (core, disc) colors, cycled through by galaxy index
+const GALAXY_COLORS: &[(&str, &str)] = &[
+    ("#FFD700", "#8888FF"),
+    ("#FF6B35", "#FF8888"),
+    ("#7CFC00", "#88FF88"),
+    ("#00CED1", "#88FFFF"),
+    ("#FF69B4", "#FF88DD"),
+    ("#DDA0DD", "#CC99FF"),
+];
+
+// The classic two-galaxy head-on pass. `retrograde` spins galaxy B against
+// A, which tears out far weaker tidal tails than the prograde default.
 pub fn generate_collision(
     state: &mut SimulationState,
     particles_per_galaxy: u32,
+    retrograde: bool,
+    arms: Option<SpiralArms>,
+    halo: Option<DarkHalo>,
+    progress: &mut dyn FnMut(usize, usize),
 ) {
-    state.clear();
-
-    let mut rng = rand::rng();
-    let particles = particles_per_galaxy.min(500);
-
-    // Galaxy 1: centered at (-400, 0, 0), moving right
-    let center1 = Vec3::new(-400.0, 0.0, 0.0);
-    let bulk_vel1 = Vec3::new(30.0, 5.0, 0.0);
-    let core_mass1 = 100000.0;
+    let galaxies = [
+        // Centered at (-400, 0, 0), moving right
+        GalaxySpec {
+            center: Vec3::new(-400.0, 0.0, 0.0),
+            bulk_velocity: Vec3::new(30.0, 5.0, 0.0),
+            core_mass: 100000.0,
+            particles: particles_per_galaxy,
+            core_radius: Some(15.0),
+            clockwise: false,
+        },
+        // Centered at (400, 0, 0), moving left
+        GalaxySpec {
+            center: Vec3::new(400.0, 0.0, 0.0),
+            bulk_velocity: Vec3::new(-30.0, -5.0, 0.0),
+            core_mass: 80000.0,
+            particles: particles_per_galaxy,
+            core_radius: Some(13.0),
+            clockwise: retrograde,
+        },
+    ];
+    generate_multi_collision(state, &galaxies, arms, halo, progress);
+}
 
-    // Galaxy 2: centered at (400, 0, 0), moving left
-    let center2 = Vec3::new(400.0, 0.0, 0.0);
-    let bulk_vel2 = Vec3::new(-30.0, -5.0, 0.0);
-    let core_mass2 = 80000.0;
+// Every galaxy gets a core, a disc and (optionally) a halo, in list order.
+// `progress` is called with (bodies generated, total) as particles are added.
+pub fn generate_multi_collision(
+    state: &mut SimulationState,
+    galaxies: &[GalaxySpec],
+    arms: Option<SpiralArms>,
+    halo: Option<DarkHalo>,
+    progress: &mut dyn FnMut(usize, usize),
+) {
+    state.clear();
+    // Dust-dominated and chaotic by nature: a coarse theta is indistinguishable
+    // from an exact solve here and keeps the tree cheap.
+    state.apply_scenario_settings(1.0, Integrator::Verlet, 10.0);
+    if galaxies.is_empty() {
+        return;
+    }
 
-    // Galaxy 1 core
-    let id = state.allocate_id();
-    state.bodies.push(CelestialBody::new(
-        id, "Galaxy A Core", center1, bulk_vel1,
-        core_mass1, 15.0, "#FFD700", false,
-    ));
+    // Every core plus its disc and halo has to fit under the body cap
+    let count = galaxies.len();
+    let cap_per_galaxy = (state.max_bodies.saturating_sub(count) / count) as u32;
+    let sizes: Vec<(u32, Option<DarkHalo>)> = galaxies
+        .iter()
+        .map(|galaxy| {
+            let particles = galaxy.particles.min(500).min(cap_per_galaxy);
+            let halo = halo.map(|h| DarkHalo {
+                mass: h.mass.max(0.0),
+                particles: h.particles.min(cap_per_galaxy - particles),
+                ..h
+            });
+            (particles, halo)
+        })
+        .collect();
+    let total: usize = sizes
+        .iter()
+        .map(|(particles, halo)| (1 + particles + halo.map_or(0, |h| h.particles)) as usize)
+        .sum();
+    let mut report = |done: usize| progress(done, total);
 
-    // Galaxy 1 particles
-    generate_disc(state, &mut rng, center1, bulk_vel1, core_mass1, particles, "A");
+    state.with_rng(|state, rng| {
+        for (index, (galaxy, (particles, halo))) in galaxies.iter().zip(sizes).enumerate() {
+            let prefix = galaxy_label(index);
+            let (core_color, disc_color) = GALAXY_COLORS[index % GALAXY_COLORS.len()];
+            let core_radius = galaxy
+                .core_radius
+                .unwrap_or_else(|| 15.0 * (galaxy.core_mass.abs() / 100000.0).cbrt());
 
-    // Galaxy 2 core
-    let id = state.allocate_id();
-    state.bodies.push(CelestialBody::new(
-        id, "Galaxy B Core", center2, bulk_vel2,
-        core_mass2, 13.0, "#FF6B35", false,
-    ));
+            let id = state.allocate_id();
+            state.bodies.push(CelestialBody::new(
+                id,
+                &format!("Galaxy {} Core", prefix),
+                galaxy.center,
+                galaxy.bulk_velocity,
+                galaxy.core_mass,
+                core_radius,
+                core_color,
+                false,
+            )
+            .with_type(BodyType::Star));
 
-    // Galaxy 2 particles
-    generate_disc(state, &mut rng, center2, bulk_vel2, core_mass2, particles, "B");
+            let disc = DiscSpec {
+                center: galaxy.center,
+                bulk_vel: galaxy.bulk_velocity,
+                core_mass: galaxy.core_mass,
+                count: particles,
+                prefix: &prefix,
+                color: disc_color,
+                clockwise: galaxy.clockwise,
+                arms,
+                halo,
+            };
+            generate_disc(state, rng, &disc, &mut report);
+            generate_halo(state, rng, &disc, &mut report);
+        }
+    });
 
     state.prime_accelerations();
 }
 
-fn generate_disc(
-    state: &mut SimulationState,
-    rng: &mut impl Rng,
+// A, B, ... Z, then AA, AB, ...
+fn galaxy_label(index: usize) -> String {
+    let letter = |i: usize| char::from(b'A' + (i % 26) as u8);
+    if index < 26 {
+        letter(index).to_string()
+    } else {
+        format!("{}{}", letter(index / 26 - 1), letter(index))
+    }
+}
+
+struct DiscSpec<'a> {
     center: Vec3,
     bulk_vel: Vec3,
     core_mass: f64,
     count: u32,
-    prefix: &str,
+    prefix: &'a str,
+    color: &'a str,
+    clockwise: bool,
+    arms: Option<SpiralArms>,
+    halo: Option<DarkHalo>,
+}
+
+fn generate_disc(
+    state: &mut SimulationState,
+    rng: &mut impl Rng,
+    disc: &DiscSpec,
+    report: &mut dyn FnMut(usize),
 ) {
+    let center = disc.center;
+    let bulk_vel = disc.bulk_vel;
+    let core_mass = disc.core_mass;
+    let prefix = disc.prefix;
     let min_r = 30.0;
     let max_r = 300.0;
 
-    for i in 0..count {
+    for i in 0..disc.count {
         // Exponential disc distribution (more particles near center)
         let u: f64 = rng.random();
         let r = min_r + (max_r - min_r) * u.sqrt();
 
-        let angle: f64 = rng.random::<f64>() * std::f64::consts::TAU;
+        let angle = match disc.arms {
+            Some(arms) if arms.count > 0 => spiral_angle(rng, r, min_r, arms),
+            _ => rng.random::<f64>() * std::f64::consts::TAU,
+        };
+        // Mirroring the disc keeps spiral arms trailing the rotation
+        let (angle, spin) = if disc.clockwise {
+            (-angle, -1.0)
+        } else {
+            (angle, 1.0)
+        };
 
         // Slight z scatter for disc thickness
         let z_scatter = (rng.random::<f64>() - 0.5) * 20.0;
@@ -70,8 +229,9 @@ fn generate_disc(
         let py = center.y + r * angle.sin();
         let pz = center.z + z_scatter;
 
-        // Circular orbital velocity around core
-        let v = (state.g * core_mass / r).sqrt();
+        // Circular orbital velocity around core (plus any halo mass inside r)
+        let enclosed = core_mass + disc.halo.map_or(0.0, |h| halo_mass_within(&h, r));
+        let v = spin * (state.g * enclosed / r).sqrt();
         let vx = bulk_vel.x - v * angle.sin();
         let vy = bulk_vel.y + v * angle.cos();
         let vz = bulk_vel.z;
@@ -79,18 +239,91 @@ fn generate_disc(
         let mass = 0.01; // Small particles
         let radius = 1.0 + rng.random::<f64>() * 0.5;
 
-        let color = if prefix == "A" { "#8888FF" } else { "#FF8888" };
-
         let id = state.allocate_id();
-        state.bodies.push(CelestialBody::new(
+        let mut particle = CelestialBody::new(
             id,
             &format!("{}{}", prefix, i),
             Vec3::new(px, py, pz),
             Vec3::new(vx, vy, vz),
             mass,
             radius,
-            color,
+            disc.color,
             false,
-        ));
+        );
+        // Disc stars are negligible next to the cores; skipping their mutual
+        // pull is what keeps 600 particles cheap on every path
+        particle.is_test_particle = true;
+        state.bodies.push(particle);
+        report(state.bodies.len());
     }
 }
+
+// Samples an azimuth at radius r by rejection against the density
+// 1 + A cos(m (theta - phi(r))), where phi(r) = ln(r / r0) / tan(pitch) traces
+// a logarithmic spiral. Every m-th of a turn gets its own arm.
+fn spiral_angle(rng: &mut impl Rng, r: f64, r0: f64, arms: SpiralArms) -> f64 {
+    let m = arms.count as f64;
+    let pitch = arms.pitch_degrees.clamp(1.0, 89.0).to_radians();
+    let phase = (r / r0).ln() / pitch.tan();
+    loop {
+        let angle = rng.random::<f64>() * std::f64::consts::TAU;
+        let density = 1.0 + ARM_CONTRAST * (m * (angle - phase)).cos();
+        if rng.random::<f64>() * (1.0 + ARM_CONTRAST) < density {
+            return angle;
+        }
+    }
+}
+
+fn halo_mass_within(halo: &DarkHalo, r: f64) -> f64 {
+    halo.mass * (r / HALO_RADIUS).min(1.0).powi(3)
+}
+
+// Uniform-density sphere of heavy particles on randomly oriented circular
+// orbits about the core, so the halo neither collapses nor flies apart
+fn generate_halo(
+    state: &mut SimulationState,
+    rng: &mut impl Rng,
+    disc: &DiscSpec,
+    report: &mut dyn FnMut(usize),
+) {
+    let Some(halo) = &disc.halo else {
+        return;
+    };
+    if halo.particles == 0 || halo.mass <= 0.0 {
+        return;
+    }
+    let (center, bulk_vel, core_mass) = (disc.center, disc.bulk_vel, disc.core_mass);
+    let particle_mass = halo.mass / halo.particles as f64;
+
+    for i in 0..halo.particles {
+        let r = HALO_RADIUS * rng.random::<f64>().cbrt();
+        let radial = random_unit(rng);
+        // Any direction perpendicular to the radius gives a circular orbit
+        let tangent = radial.cross(&random_unit(rng)).normalize();
+        let v = (state.g * (core_mass + halo_mass_within(halo, r)) / r).sqrt();
+
+        let id = state.allocate_id();
+        let mut particle = CelestialBody::new(
+            id,
+            &format!("{} Halo {}", disc.prefix, i),
+            center + radial.scale(r),
+            bulk_vel + tangent.scale(v),
+            particle_mass,
+            0.5,
+            "#553377",
+            false,
+        );
+        particle.hidden = !halo.visible;
+        // Halo orbits are not interesting to draw and there are many of them
+        particle.record_trail_enabled = false;
+        state.bodies.push(particle);
+        report(state.bodies.len());
+    }
+}
+
+fn random_unit(rng: &mut impl Rng) -> Vec3 {
+    let z: f64 = rng.random::<f64>() * 2.0 - 1.0;
+    let phi = rng.random::<f64>() * std::f64::consts::TAU;
+    let s = (1.0 - z * z).sqrt();
+    Vec3::new(s * phi.cos(), s * phi.sin(), z)
+}
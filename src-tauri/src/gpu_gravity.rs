@@ -1,50 +1,91 @@
-use crate::physics::Vec3;
+use crate::physics::{SofteningKernel, Vec3};
 use std::sync::Arc;
 use wgpu::util::DeviceExt;
 
 const SHADER_SOURCE: &str = r#"
+// Positions are relative to the batch centroid, split into an f32 `p` and
+// the f32 residual `l` left over from the f64 value
 struct Body {
     px: f32, py: f32, pz: f32, mass: f32,
+    lx: f32, ly: f32, lz: f32, pad: f32,
 };
 
 struct Params {
     count: u32,
     g: f32,
     softening_sq: f32,
-    _pad: u32,
+    kernel: u32, // 0 = Plummer, 1 = cubic spline
+    two_float: u32, // 1 = use the residuals and compensated sums
+    pad0: u32,
+    pad1: u32,
+    pad2: u32,
 };
 
 @group(0) @binding(0) var<storage, read> bodies: array<Body>;
 @group(0) @binding(1) var<storage, read_write> accels: array<vec4<f32>>;
 @group(0) @binding(2) var<uniform> params: Params;
 
+// Mirrors SofteningKernel::inv_r3 on the CPU
+fn inv_r3(r2: f32) -> f32 {
+    if (params.kernel == 0u) {
+        let inv = inverseSqrt(r2 + params.softening_sq);
+        return inv * inv * inv;
+    }
+    let h = sqrt(params.softening_sq);
+    let r = sqrt(r2);
+    if (r >= h) {
+        return 1.0 / (r2 * r);
+    }
+    let u = r / h;
+    let h_inv3 = 1.0 / (h * h * h);
+    if (u < 0.5) {
+        return h_inv3 * (10.666666667 + u * u * (32.0 * u - 38.4));
+    }
+    return h_inv3 * (21.333333333 - 48.0 * u + 38.4 * u * u
+        - 10.666666667 * u * u * u - 0.066666667 / (u * u * u));
+}
+
+// Large batches are dispatched as several rows of workgroups (see
+// `dispatch_grid`), so the index runs along x and then on to the next row
 @compute @workgroup_size(64)
-fn main(@builtin(global_invocation_id) gid: vec3<u32>) {
-    let i = gid.x;
+fn main(
+    @builtin(global_invocation_id) gid: vec3<u32>,
+    @builtin(num_workgroups) groups: vec3<u32>,
+) {
+    let i = gid.y * groups.x * 64u + gid.x;
     if (i >= params.count) { return; }
 
-    var ax: f32 = 0.0;
-    var ay: f32 = 0.0;
-    var az: f32 = 0.0;
+    let two_float = params.two_float == 1u;
+    var acc = vec3<f32>(0.0, 0.0, 0.0);
+    // Kahan compensation: the low-order part lost by each addition
+    var lost = vec3<f32>(0.0, 0.0, 0.0);
 
     let pi = bodies[i];
 
     for (var j: u32 = 0u; j < params.count; j++) {
         if (j == i) { continue; }
         let pj = bodies[j];
-        let dx = pj.px - pi.px;
-        let dy = pj.py - pi.py;
-        let dz = pj.pz - pi.pz;
-        let dist_sq = dx * dx + dy * dy + dz * dz + params.softening_sq;
-        let inv_dist = inverseSqrt(dist_sq);
-        let inv_dist3 = inv_dist * inv_dist * inv_dist;
-        let f = params.g * pj.mass * inv_dist3;
-        ax += dx * f;
-        ay += dy * f;
-        az += dz * f;
+        // Test particles are packed with zero mass and exert no pull
+        if (pj.mass == 0.0) { continue; }
+        // For nearby bodies the high parts cancel exactly, so adding the
+        // residual difference recovers what the f32 rounding dropped
+        var d = vec3<f32>(pj.px - pi.px, pj.py - pi.py, pj.pz - pi.pz);
+        if (two_float) {
+            d += vec3<f32>(pj.lx - pi.lx, pj.ly - pi.ly, pj.lz - pi.lz);
+        }
+        let f = params.g * pj.mass * inv_r3(dot(d, d));
+        let term = d * f;
+        if (two_float) {
+            let y = term - lost;
+            let t = acc + y;
+            lost = (t - acc) - y;
+            acc = t;
+        } else {
+            acc += term;
+        }
     }
 
-    accels[i] = vec4<f32>(ax, ay, az, 0.0);
+    accels[i] = vec4<f32>(acc, 0.0);
 }
 "#;
 
@@ -53,16 +94,83 @@ pub struct GpuGravity {
     queue: Arc<wgpu::Queue>,
     pipeline: wgpu::ComputePipeline,
     bind_group_layout: wgpu::BindGroupLayout,
+    // Device limits queried at init
+    max_workgroups_per_dim: u32,
+    max_binding_size: u64,
+}
+
+// Must match @workgroup_size in the shader
+const WORKGROUP_SIZE: u32 = 64;
+
+// Bytes per body in the packed input buffer
+const BODY_STRIDE: u64 = 8 * std::mem::size_of::<f32>() as u64;
+
+// Workgroup grid covering `n` invocations. Past the per-dimension limit the
+// groups wrap into further rows along y.
+fn dispatch_grid(n: usize, max_per_dim: u32) -> (u32, u32) {
+    let groups = (n as u64).div_ceil(WORKGROUP_SIZE as u64);
+    let max_per_dim = max_per_dim.max(1) as u64;
+    if groups <= max_per_dim {
+        (groups as u32, 1)
+    } else {
+        (max_per_dim as u32, groups.div_ceil(max_per_dim) as u32)
+    }
+}
+
+// Set to vulkan, metal, dx12 or gl to force one backend when debugging
+const BACKEND_ENV: &str = "ORBITFORGE_GPU_BACKEND";
+
+fn requested_backends() -> Result<wgpu::Backends, String> {
+    let Ok(name) = std::env::var(BACKEND_ENV) else {
+        return Ok(wgpu::Backends::all());
+    };
+    match name.to_ascii_lowercase().as_str() {
+        "vulkan" => Ok(wgpu::Backends::VULKAN),
+        "metal" => Ok(wgpu::Backends::METAL),
+        "dx12" => Ok(wgpu::Backends::DX12),
+        "gl" => Ok(wgpu::Backends::GL),
+        other => Err(format!("unknown {} value '{}'", BACKEND_ENV, other)),
+    }
 }
 
 impl GpuGravity {
-    pub fn new() -> Option<Self> {
-        let instance = wgpu::Instance::default();
-        let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
-            power_preference: wgpu::PowerPreference::HighPerformance,
+    // Tries a discrete GPU first, then an integrated one. The error says why
+    // no device could be set up so the fallback to CPU is diagnosable.
+    pub fn new() -> Result<Self, String> {
+        let backends = requested_backends()?;
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
+            backends,
             ..Default::default()
-        }))?;
+        });
 
+        let mut failures = Vec::new();
+        let preferences = [
+            wgpu::PowerPreference::HighPerformance,
+            wgpu::PowerPreference::LowPower,
+        ];
+        for power_preference in preferences {
+            let Some(adapter) = pollster::block_on(instance.request_adapter(
+                &wgpu::RequestAdapterOptions {
+                    power_preference,
+                    ..Default::default()
+                },
+            )) else {
+                failures.push(format!("{:?}: no adapter", power_preference));
+                continue;
+            };
+            let info = adapter.get_info();
+            match Self::with_adapter(&adapter) {
+                Ok(gpu) => {
+                    println!("GPU gravity using {} ({:?})", info.name, info.backend);
+                    return Ok(gpu);
+                }
+                Err(e) => failures.push(format!("{} ({:?}): {}", info.name, info.backend, e)),
+            }
+        }
+        Err(format!("backends {:?}: {}", backends, failures.join("; ")))
+    }
+
+    fn with_adapter(adapter: &wgpu::Adapter) -> Result<Self, String> {
         let (device, queue) = pollster::block_on(adapter.request_device(
             &wgpu::DeviceDescriptor {
                 label: Some("gravity_device"),
@@ -71,7 +179,8 @@ impl GpuGravity {
                 ..Default::default()
             },
             None,
-        )).ok()?;
+        ))
+        .map_err(|e| format!("device request failed: {}", e))?;
 
         let device = Arc::new(device);
         let queue = Arc::new(queue);
@@ -132,33 +241,60 @@ impl GpuGravity {
             cache: None,
         });
 
-        Some(Self {
+        let limits = device.limits();
+        Ok(Self {
             device,
             queue,
             pipeline,
             bind_group_layout,
+            max_workgroups_per_dim: limits.max_compute_workgroups_per_dimension,
+            max_binding_size: limits.max_storage_buffer_binding_size as u64,
         })
     }
 
+    // Most bodies one dispatch can handle on this device: a full grid of
+    // workgroups, or a body buffer as large as a binding may be, whichever is
+    // smaller. Bigger batches have to go to the CPU.
+    pub fn max_bodies(&self) -> usize {
+        let per_dim = self.max_workgroups_per_dim as u64;
+        let by_grid = per_dim * per_dim * WORKGROUP_SIZE as u64;
+        let by_buffer = self.max_binding_size / BODY_STRIDE;
+        by_grid.min(by_buffer).min(usize::MAX as u64) as usize
+    }
+
     pub fn compute_accelerations(
         &self,
         positions: &[Vec3],
         masses: &[f64],
         g: f64,
         softening_sq: f64,
+        kernel: SofteningKernel,
+        precise: bool,
     ) -> Vec<Vec3> {
         let n = positions.len();
         if n == 0 {
             return Vec::new();
         }
 
-        // Pack body data: [px, py, pz, mass] as f32
-        let mut body_data: Vec<f32> = Vec::with_capacity(n * 4);
-        for i in 0..n {
-            body_data.push(positions[i].x as f32);
-            body_data.push(positions[i].y as f32);
-            body_data.push(positions[i].z as f32);
-            body_data.push(masses[i] as f32);
+        // Shifting to the centroid keeps coordinates small before the f32
+        // cast, so a scene far from the origin loses no extra precision
+        let mut centroid = Vec3::zero();
+        for p in positions {
+            centroid += *p;
+        }
+        let centroid = centroid.scale(1.0 / n as f64);
+
+        // Pack body data: [px, py, pz, mass, lx, ly, lz, 0] as f32
+        let mut body_data: Vec<f32> = Vec::with_capacity(n * 8);
+        for (p, &mass) in positions.iter().zip(masses.iter()) {
+            let rel = *p - centroid;
+            let high = [rel.x as f32, rel.y as f32, rel.z as f32];
+            body_data.extend_from_slice(&high);
+            body_data.push(mass as f32);
+            body_data.push((rel.x - high[0] as f64) as f32);
+            body_data.push((rel.y - high[1] as f64) as f32);
+            body_data.push((rel.z - high[2] as f64) as f32);
+            body_data.push(0.0);
         }
 
         let body_buf = self.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
@@ -182,13 +318,16 @@ impl GpuGravity {
             mapped_at_creation: false,
         });
 
-        // Params: count (u32), g (f32), softening_sq (f32), pad (u32)
+        // Params: count (u32), g (f32), softening_sq (f32), kernel (u32),
+        // two_float (u32), then padding to the 16-byte uniform size rule.
         // Pack as raw bytes to handle mixed u32/f32
-        let mut params_bytes = Vec::with_capacity(16);
+        let mut params_bytes = Vec::with_capacity(32);
         params_bytes.extend_from_slice(&(n as u32).to_le_bytes());
         params_bytes.extend_from_slice(&(g as f32).to_le_bytes());
         params_bytes.extend_from_slice(&(softening_sq as f32).to_le_bytes());
-        params_bytes.extend_from_slice(&0u32.to_le_bytes());
+        params_bytes.extend_from_slice(&kernel.shader_id().to_le_bytes());
+        params_bytes.extend_from_slice(&(precise as u32).to_le_bytes());
+        params_bytes.extend_from_slice(&[0; 12]);
         let params_data = params_bytes;
         let params_buf = self.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("params_buf"),
@@ -211,8 +350,8 @@ impl GpuGravity {
             let mut pass = encoder.begin_compute_pass(&Default::default());
             pass.set_pipeline(&self.pipeline);
             pass.set_bind_group(0, &bind_group, &[]);
-            let workgroups = ((n as u32) + 63) / 64;
-            pass.dispatch_workgroups(workgroups, 1, 1);
+            let (x, y) = dispatch_grid(n, self.max_workgroups_per_dim);
+            pass.dispatch_workgroups(x, y, 1);
         }
         encoder.copy_buffer_to_buffer(&accel_buf, 0, &readback_buf, 0, accel_size);
         self.queue.submit(std::iter::once(encoder.finish()));
@@ -242,3 +381,81 @@ impl GpuGravity {
     }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::SimRng;
+    use rand::Rng;
+
+    const G: f64 = 1.0;
+    const SOFTENING_SQ: f64 = 0.25;
+
+    fn brute_force(positions: &[Vec3], masses: &[f64]) -> Vec<Vec3> {
+        let kernel = SofteningKernel::Plummer;
+        positions
+            .iter()
+            .enumerate()
+            .map(|(i, p)| {
+                let mut accel = Vec3::zero();
+                for (j, (q, &m)) in positions.iter().zip(masses).enumerate() {
+                    if j != i {
+                        let diff = *q - *p;
+                        accel += diff.scale(G * m * kernel.inv_r3(diff.dot(&diff), SOFTENING_SQ));
+                    }
+                }
+                accel
+            })
+            .collect()
+    }
+
+    // RMS error over the RMS of the f64 field
+    fn relative_error(gpu: &[Vec3], cpu: &[Vec3]) -> f64 {
+        let err: f64 = gpu.iter().zip(cpu).map(|(a, b)| (*a - *b).dot(&(*a - *b))).sum();
+        let norm: f64 = cpu.iter().map(|a| a.dot(a)).sum();
+        (err / norm).sqrt()
+    }
+
+    #[test]
+    fn gpu_matches_cpu_far_from_the_origin() {
+        let Ok(gpu) = GpuGravity::new() else {
+            eprintln!("no GPU adapter, skipping");
+            return;
+        };
+        // A disc of ±400 with a ±300 cloud through it, the shape that used to
+        // lose precision in the f32 cast
+        let mut rng = SimRng::from_seed(636);
+        let mut local = Vec::new();
+        for _ in 0..1500 {
+            let mut coord = || rng.random_range(-400.0..400.0);
+            local.push(Vec3::new(coord(), coord(), coord() * 0.02));
+        }
+        for _ in 0..1500 {
+            let mut coord = || rng.random_range(-300.0..300.0);
+            local.push(Vec3::new(coord(), coord(), coord()));
+        }
+        let masses: Vec<f64> = (0..local.len()).map(|_| rng.random_range(0.1..10.0)).collect();
+        let cpu = brute_force(&local, &masses);
+
+        let errors = |offset: f64| {
+            let shift = Vec3::new(offset, -offset, offset);
+            let positions: Vec<Vec3> = local.iter().map(|p| *p + shift).collect();
+            let run = |precise| {
+                let kernel = SofteningKernel::Plummer;
+                gpu.compute_accelerations(&positions, &masses, G, SOFTENING_SQ, kernel, precise)
+            };
+            (relative_error(&run(false), &cpu), relative_error(&run(true), &cpu))
+        };
+        let (fast, precise) = errors(0.0);
+        assert!(fast < 1e-4, "fast mode error {fast}");
+        assert!(precise < 1e-5, "precise mode error {precise}");
+        assert!(precise < fast, "precise {precise} no better than fast {fast}");
+
+        // Centred on the batch, the same scene a million units out loses
+        // nothing; cast directly, f32 there resolves only about a tenth of a
+        // unit
+        let (far_fast, far_precise) = errors(1.0e6);
+        assert!(far_fast < 1.1 * fast, "fast mode error {far_fast} far out");
+        assert!(far_precise < 1.1 * precise, "precise mode error {far_precise} far out");
+    }
+}
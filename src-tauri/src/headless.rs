@@ -0,0 +1,72 @@
+// Batch runner for parameter sweeps: steps a state with no Tauri event loop
+// and writes periodic snapshots as JSON lines, one object per snapshot.
+
+use crate::physics::Vec3;
+use crate::simulation::{EnergyData, SimulationState};
+use serde::Serialize;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+#[derive(Serialize)]
+struct Snapshot<'a> {
+    tick: u64,
+    sim_time: f64,
+    energy: EnergyData,
+    bodies: Vec<BodyPosition<'a>>,
+}
+
+#[derive(Serialize)]
+struct BodyPosition<'a> {
+    id: u32,
+    name: &'a str,
+    position: Vec3,
+    velocity: Vec3,
+}
+
+// Runs `steps` calls to `SimulationState::step` (so speed multiplier and
+// substeps behave exactly as in the app) and snapshots the initial state
+// and every `snapshot_every` steps after it. Returns the final state.
+pub fn run_headless(
+    mut state: SimulationState,
+    steps: u64,
+    output_path: &Path,
+    snapshot_every: u64,
+) -> std::io::Result<SimulationState> {
+    let mut out = BufWriter::new(File::create(output_path)?);
+    let every = snapshot_every.max(1);
+
+    state.paused = false;
+    state.prime_accelerations();
+    write_snapshot(&mut out, &state)?;
+
+    for step in 1..=steps {
+        state.step();
+        if step % every == 0 || step == steps {
+            write_snapshot(&mut out, &state)?;
+        }
+    }
+
+    out.flush()?;
+    Ok(state)
+}
+
+fn write_snapshot(out: &mut impl Write, state: &SimulationState) -> std::io::Result<()> {
+    let snapshot = Snapshot {
+        tick: state.tick,
+        sim_time: state.sim_time,
+        energy: state.compute_energies(),
+        bodies: state
+            .bodies
+            .iter()
+            .map(|b| BodyPosition {
+                id: b.id,
+                name: &b.name,
+                position: b.position,
+                velocity: b.velocity,
+            })
+            .collect(),
+    };
+    serde_json::to_writer(&mut *out, &snapshot)?;
+    writeln!(out)
+}
@@ -2,13 +2,24 @@ mod barneshut;
 mod commands;
 mod galaxy;
 mod gpu_gravity;
+pub mod headless;
 mod physics;
+mod pm;
 mod procedural;
+mod rng;
 mod scenarios;
+#[cfg(feature = "server")]
+mod server;
 mod simulation;
 
 use commands::SimState;
-use simulation::SimulationState;
+pub use headless::run_headless;
+pub use scenarios::load_by_name;
+pub use physics::{BodyType, CelestialBody, Vec3};
+pub use simulation::{
+    CollisionConfig, CollisionMode, FrameUpdate, Integrator, SimulationFrame, SimulationState,
+    SimulationStateBuilder, SofteningMode, SolverKind, StepHook,
+};
 use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time::{Duration, Instant};
@@ -22,14 +33,18 @@ pub fn run() {
     {
         let mut sim = sim_state.lock().unwrap();
         match gpu_gravity::GpuGravity::new() {
-            Some(gpu) => {
+            Ok(gpu) => {
                 sim.gpu = Some(Arc::new(gpu));
                 println!("GPU gravity compute initialized");
             }
-            None => {
-                println!("GPU gravity not available, using CPU");
+            Err(e) => {
+                println!("GPU gravity not available ({}), using CPU", e);
             }
         }
+        let threads = simulation::default_physics_threads();
+        if let Err(e) = sim.set_physics_threads(threads) {
+            println!("Physics thread pool not available ({}), using rayon's", e);
+        }
     }
 
     // Load default scenario
@@ -38,27 +53,109 @@ pub fn run() {
         scenarios::load_sun_earth(&mut sim);
     }
 
+    #[cfg(feature = "server")]
+    {
+        let addr = std::env::var("ORBITFORGE_SERVER_ADDR")
+            .unwrap_or_else(|_| "127.0.0.1:7878".to_string());
+        match server::spawn(sim_state.clone(), &addr) {
+            Ok(()) => println!("Control server listening on ws://{}", addr),
+            Err(e) => println!("Control server failed to start: {}", e),
+        }
+    }
+
     tauri::Builder::default()
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_fs::init())
         .manage(sim_state.clone() as SimState)
         .invoke_handler(tauri::generate_handler![
             commands::toggle_pause,
+            commands::step_once,
             commands::set_speed,
+            commands::set_substeps,
+            commands::set_physics_threads,
+            commands::set_settling,
             commands::load_test_scenario,
             commands::clear_simulation,
             commands::add_body,
+            commands::add_bodies,
+            commands::add_orbiting_body,
+            commands::add_disc,
+            commands::set_ghost_body,
+            commands::clear_ghost_body,
+            commands::commit_ghost_body,
             commands::remove_body,
+            commands::collapse_system,
+            commands::uncollapse_system,
+            commands::set_body_group,
+            commands::remove_group,
+            commands::translate_group,
+            commands::recolor_group,
+            commands::get_body,
             commands::update_body,
             commands::update_body_velocity,
+            commands::apply_impulse,
+            commands::reprime,
+            commands::reverse,
+            commands::list_scenarios,
             commands::load_scenario,
+            commands::save_named_scenario,
+            commands::append_scenario,
+            commands::rotate_system,
+            commands::auto_color,
             commands::predict_orbit,
+            commands::predict_orbit_from,
+            commands::set_snapshot_interval,
+            commands::get_snapshots,
+            commands::get_hill_radius,
+            commands::get_dominant_body,
+            commands::get_momentum,
             commands::export_state,
+            commands::set_view_state,
+            commands::get_view_state,
+            commands::export_trails,
             commands::import_state,
             commands::set_spacecraft_thrust,
+            commands::set_exhaust,
+            commands::set_spin,
             commands::generate_system,
             commands::load_galaxy_collision,
+            commands::load_multi_galaxy_collision,
+            commands::set_dt,
             commands::set_theta,
+            commands::recenter_tree,
+            commands::release_tree_bounds,
+            commands::set_gravity,
+            commands::set_softening,
+            commands::validate_stability,
+            commands::set_integrator,
+            commands::set_softening_mode,
+            commands::set_softening_kernel,
+            commands::set_smooth_accretion,
+            commands::set_force_exponent,
+            commands::set_relativistic,
+            commands::set_focus_body,
+            commands::set_trail_frame,
+            commands::set_max_bodies,
+            commands::set_allow_negative_mass,
+            commands::set_nan_guard,
+            commands::set_solver,
+            commands::set_gpu_enabled,
+            commands::set_gpu_precise,
+            commands::set_accel_thresholds,
+            commands::set_period_trails,
+            commands::set_orbit_info,
+            commands::set_frame_lod,
+            commands::set_periodic,
+            commands::set_max_speed,
+            commands::set_rng_seed,
+            commands::set_collision_config,
+            commands::get_collision_stats,
+            commands::get_active_solver,
+            commands::get_speed_range,
+            commands::reset_speed_range,
+            commands::set_precession_tracking,
+            commands::get_precession_rate,
+            commands::explain_acceleration,
         ])
         .setup(move |app| {
             let handle = app.handle().clone();
@@ -68,19 +165,22 @@ pub fn run() {
                 loop {
                     let start = Instant::now();
 
-                    let (frame, collisions) = {
-                        let mut sim = state_clone.lock().unwrap();
-                        let collisions = sim.step();
-                        let frame = sim.to_frame();
-                        (frame, collisions)
-                    };
+                    let update = state_clone.lock().unwrap().next_frame();
 
-                    let _ = handle.emit("simulation-state", &frame);
+                    let _ = handle.emit("simulation-state", &update.frame);
 
-                    for collision in &collisions {
+                    for collision in &update.collisions {
                         let _ = handle.emit("collision", collision);
                     }
 
+                    for error in &update.numerical_errors {
+                        let _ = handle.emit("numerical-error", error);
+                    }
+
+                    for clamp in &update.speed_clamps {
+                        let _ = handle.emit("speed-clamped", clamp);
+                    }
+
                     let elapsed = start.elapsed();
                     if elapsed < tick_duration {
                         thread::sleep(tick_duration - elapsed);
@@ -1,7 +1,9 @@
 use serde::{Deserialize, Serialize};
 use std::collections::VecDeque;
 
-const MAX_TRAIL_POINTS: usize = 500;
+pub const MAX_TRAIL_POINTS: usize = 500;
+// Fuel burned per unit of impulse (|thrust| * dt) a spacecraft delivers
+pub const FUEL_PER_IMPULSE: f64 = 0.1;
 
 #[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
 pub struct TrailPoint {
@@ -9,6 +11,83 @@ pub struct TrailPoint {
     pub y: f64,
     pub z: f64,
     pub speed: f64,
+    // Simulation time the point was recorded at
+    #[serde(default)]
+    pub time: f64,
+    // Acceleration magnitude there: large where the path bends hard, e.g. at
+    // the closest point of a slingshot
+    #[serde(default)]
+    pub accel: f64,
+}
+
+impl TrailPoint {
+    fn position(&self) -> Vec3 {
+        Vec3::new(self.x, self.y, self.z)
+    }
+}
+
+// Keeps every `n`th point, always including the last so the trail still ends
+// at the body
+pub fn decimate_trail(points: &[TrailPoint], n: usize) -> Vec<TrailPoint> {
+    let n = n.max(1);
+    let mut kept: Vec<TrailPoint> = points.iter().step_by(n).copied().collect();
+    if let Some(last) = points.last() {
+        if !(points.len() - 1).is_multiple_of(n) {
+            kept.push(*last);
+        }
+    }
+    kept
+}
+
+// Douglas-Peucker: drops points lying within `tolerance` of the line between
+// the points kept around them, so straight stretches collapse and curves keep
+// their shape. Endpoints are always kept.
+pub fn simplify_trail(points: &[TrailPoint], tolerance: f64) -> Vec<TrailPoint> {
+    if points.len() < 3 {
+        return points.to_vec();
+    }
+    let mut keep = vec![false; points.len()];
+    keep[0] = true;
+    keep[points.len() - 1] = true;
+
+    // Explicit stack: long trails would recurse deeply
+    let mut spans = vec![(0, points.len() - 1)];
+    while let Some((first, last)) = spans.pop() {
+        let a = points[first].position();
+        let b = points[last].position();
+        let mut farthest = first;
+        let mut max_dist = 0.0;
+        for (i, point) in points.iter().enumerate().take(last).skip(first + 1) {
+            let dist = segment_distance(&point.position(), &a, &b);
+            if dist > max_dist {
+                max_dist = dist;
+                farthest = i;
+            }
+        }
+        if max_dist > tolerance {
+            keep[farthest] = true;
+            spans.push((first, farthest));
+            spans.push((farthest, last));
+        }
+    }
+
+    points
+        .iter()
+        .zip(keep)
+        .filter(|(_, k)| *k)
+        .map(|(p, _)| *p)
+        .collect()
+}
+
+fn segment_distance(p: &Vec3, a: &Vec3, b: &Vec3) -> f64 {
+    let ab = *b - *a;
+    let len_sq = ab.dot(&ab);
+    let t = if len_sq > 0.0 {
+        ((*p - *a).dot(&ab) / len_sq).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    (*p - (*a + ab.scale(t))).magnitude()
 }
 
 #[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
@@ -31,7 +110,6 @@ impl Vec3 {
         (self.x * self.x + self.y * self.y + self.z * self.z).sqrt()
     }
 
-    #[allow(dead_code)]
     pub fn normalize(&self) -> Self {
         let mag = self.magnitude();
         if mag == 0.0 {
@@ -52,12 +130,10 @@ impl Vec3 {
         }
     }
 
-    #[allow(dead_code)]
     pub fn dot(&self, other: &Self) -> f64 {
         self.x * other.x + self.y * other.y + self.z * other.z
     }
 
-    #[allow(dead_code)]
     pub fn cross(&self, other: &Self) -> Self {
         Self {
             x: self.y * other.z - self.z * other.y,
@@ -65,6 +141,12 @@ impl Vec3 {
             z: self.x * other.y - self.y * other.x,
         }
     }
+
+    // Rodrigues' rotation by `angle` radians about the unit vector `axis`
+    pub fn rotate(&self, axis: &Vec3, angle: f64) -> Self {
+        let (sin, cos) = angle.sin_cos();
+        self.scale(cos) + axis.cross(self).scale(sin) + axis.scale(axis.dot(self) * (1.0 - cos))
+    }
 }
 
 impl std::ops::Add for Vec3 {
@@ -97,6 +179,197 @@ impl std::ops::AddAssign for Vec3 {
     }
 }
 
+// Unit quaternion used for a body's orientation (w is the scalar part)
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Quat {
+    pub w: f64,
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+}
+
+impl Default for Quat {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+impl Quat {
+    pub fn identity() -> Self {
+        Self { w: 1.0, x: 0.0, y: 0.0, z: 0.0 }
+    }
+
+    pub fn from_axis_angle(axis: &Vec3, angle: f64) -> Self {
+        let axis = axis.normalize();
+        let (s, c) = (angle * 0.5).sin_cos();
+        Self {
+            w: c,
+            x: axis.x * s,
+            y: axis.y * s,
+            z: axis.z * s,
+        }
+    }
+
+    pub fn mul(&self, rhs: &Self) -> Self {
+        Self {
+            w: self.w * rhs.w - self.x * rhs.x - self.y * rhs.y - self.z * rhs.z,
+            x: self.w * rhs.x + self.x * rhs.w + self.y * rhs.z - self.z * rhs.y,
+            y: self.w * rhs.y - self.x * rhs.z + self.y * rhs.w + self.z * rhs.x,
+            z: self.w * rhs.z + self.x * rhs.y - self.y * rhs.x + self.z * rhs.w,
+        }
+    }
+
+    pub fn normalize(&self) -> Self {
+        let mag = (self.w * self.w + self.x * self.x + self.y * self.y + self.z * self.z).sqrt();
+        if mag == 0.0 {
+            return Self::identity();
+        }
+        Self {
+            w: self.w / mag,
+            x: self.x / mag,
+            y: self.y / mag,
+            z: self.z / mag,
+        }
+    }
+}
+
+// Shape of the softened force law at short range
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SofteningKernel {
+    // 1 / (r^2 + eps^2)^(3/2): smooth everywhere, never exactly Newtonian
+    #[default]
+    Plummer,
+    // Cubic spline (Monaghan/Gadget) with support equal to the softening
+    // length: exactly Newtonian beyond it, finite at zero separation
+    Spline,
+}
+
+impl SofteningKernel {
+    // Factor f such that the acceleration toward a mass m at offset d is
+    // G * m * f * d, given the unsoftened squared distance
+    pub fn inv_r3(&self, dist_sq: f64, softening_sq: f64) -> f64 {
+        match self {
+            SofteningKernel::Plummer => {
+                let soft = dist_sq + softening_sq;
+                1.0 / (soft * soft.sqrt())
+            }
+            SofteningKernel::Spline => {
+                let h = softening_sq.sqrt();
+                let r = dist_sq.sqrt();
+                if r >= h {
+                    return 1.0 / (dist_sq * r);
+                }
+                let u = r / h;
+                let h_inv3 = 1.0 / (h * h * h);
+                if u < 0.5 {
+                    h_inv3 * (32.0 / 3.0 + u * u * (32.0 * u - 38.4))
+                } else {
+                    h_inv3
+                        * (64.0 / 3.0 - 48.0 * u + 38.4 * u * u - 32.0 / 3.0 * u * u * u
+                            - 1.0 / (15.0 * u * u * u))
+                }
+            }
+        }
+    }
+
+    // Tag the GPU shader switches on
+    pub fn shader_id(&self) -> u32 {
+        match self {
+            SofteningKernel::Plummer => 0,
+            SofteningKernel::Spline => 1,
+        }
+    }
+}
+
+// RGB channels in [0, 1] from the two formats bodies are colored with:
+// `#rrggbb` (or `#rgb`) and `hsl(h, s%, l%)`
+fn parse_color(color: &str) -> Option<[f64; 3]> {
+    let color = color.trim();
+    if let Some(hex) = color.strip_prefix('#') {
+        let channel = |s: &str| u8::from_str_radix(s, 16).ok().map(|v| v as f64 / 255.0);
+        return match hex.len() {
+            6 => Some([channel(&hex[0..2])?, channel(&hex[2..4])?, channel(&hex[4..6])?]),
+            3 => {
+                let short = |i: usize| channel(&hex[i..i + 1]).map(|v| v * 17.0);
+                Some([short(0)?, short(1)?, short(2)?])
+            }
+            _ => None,
+        };
+    }
+    let inner = color.strip_prefix("hsl(")?.strip_suffix(')')?;
+    let mut parts = inner.split(',').map(|p| p.trim().trim_end_matches('%').parse::<f64>());
+    let h = parts.next()?.ok()?.rem_euclid(360.0);
+    let s = (parts.next()?.ok()? / 100.0).clamp(0.0, 1.0);
+    let l = (parts.next()?.ok()? / 100.0).clamp(0.0, 1.0);
+    if parts.next().is_some() {
+        return None;
+    }
+    let chroma = (1.0 - (2.0 * l - 1.0).abs()) * s;
+    let x = chroma * (1.0 - ((h / 60.0) % 2.0 - 1.0).abs());
+    let (r, g, b) = match (h / 60.0) as u32 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+    let m = l - chroma / 2.0;
+    Some([r + m, g + m, b + m])
+}
+
+// Weighted RGB average of two body colors as `#rrggbb`; None if either can't
+// be parsed or the weights sum to zero
+pub fn blend_colors(a: &str, weight_a: f64, b: &str, weight_b: f64) -> Option<String> {
+    let (a, b) = (parse_color(a)?, parse_color(b)?);
+    let total = weight_a + weight_b;
+    if total <= 0.0 || !total.is_finite() {
+        return None;
+    }
+    let channel = |i: usize| {
+        let v = (a[i] * weight_a + b[i] * weight_b) / total;
+        (v.clamp(0.0, 1.0) * 255.0).round() as u8
+    };
+    Some(format!("#{:02X}{:02X}{:02X}", channel(0), channel(1), channel(2)))
+}
+
+// Osculating two-body orbit of one body relative to another
+#[derive(Debug, Clone, Copy)]
+pub struct TwoBodyOrbit {
+    // v^2/2 - mu/r; negative when bound
+    pub specific_energy: f64,
+    // None for parabolic/hyperbolic (unbound) orbits
+    pub semi_major_axis: Option<f64>,
+    pub eccentricity: f64,
+}
+
+impl TwoBodyOrbit {
+    // Kepler period 2 pi sqrt(a^3 / mu); None when unbound
+    pub fn period(&self, mu: f64) -> Option<f64> {
+        self.semi_major_axis
+            .map(|a| std::f64::consts::TAU * (a * a * a / mu).sqrt())
+    }
+}
+
+// `mu` is G * (M + m); position and velocity are relative to the primary
+pub fn two_body_orbit(mu: f64, rel_pos: &Vec3, rel_vel: &Vec3) -> TwoBodyOrbit {
+    let r = rel_pos.magnitude();
+    let v2 = rel_vel.dot(rel_vel);
+    let specific_energy = 0.5 * v2 - mu / r;
+    let semi_major_axis = if specific_energy < 0.0 {
+        Some(-mu / (2.0 * specific_energy))
+    } else {
+        None
+    };
+    let e_vec = (rel_pos.scale(v2 - mu / r) - rel_vel.scale(rel_pos.dot(rel_vel))).scale(1.0 / mu);
+    TwoBodyOrbit {
+        specific_energy,
+        semi_major_axis,
+        eccentricity: e_vec.magnitude(),
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum BodyType {
@@ -116,6 +389,10 @@ pub struct CelestialBody {
     pub id: u32,
     pub position: Vec3,
     pub velocity: Vec3,
+    // Total acceleration at the current position: gravity plus any active
+    // thrust. The integrator refreshes it at the end of every substep, so a
+    // frame shows exactly what the body felt in its last velocity kick (and
+    // what it will drift with next).
     #[serde(default)]
     pub acceleration: Vec3,
     pub mass: f64,
@@ -132,6 +409,48 @@ pub struct CelestialBody {
     pub fuel: f64,
     #[serde(default = "default_fuel")]
     pub max_fuel: f64,
+    // Intrinsic spin (rad per time unit, world frame). Purely kinematic: it is
+    // integrated into `orientation` but never feeds back into the forces.
+    #[serde(default)]
+    pub angular_velocity: Vec3,
+    #[serde(default)]
+    pub orientation: Quat,
+    // Test particles feel gravity but exert none, on every acceleration path
+    #[serde(default)]
+    pub is_test_particle: bool,
+    // Rendering hint only (e.g. dark-matter halo particles); physics ignores it
+    #[serde(default)]
+    pub hidden: bool,
+    // Fixed bodies never record trails regardless of this flag
+    #[serde(default = "default_record_trail")]
+    pub record_trail_enabled: bool,
+    // Points kept before the oldest drop off. Fixed unless period-fitted
+    // trails are on.
+    #[serde(default = "default_trail_max")]
+    pub trail_max: usize,
+    // Comet outgassing: a push of `nongrav / r^2` straight away from the
+    // nearest star. None for ordinary bodies.
+    #[serde(default)]
+    pub nongrav: Option<f64>,
+    // Editor label for acting on several bodies at once; physics ignores it
+    #[serde(default)]
+    pub group: Option<String>,
+    // Protected from merging: contacts involving this body bounce instead,
+    // whatever the collision mode
+    #[serde(default)]
+    pub no_merge: bool,
+    // Ticks left before the body is removed; None lives forever. Bodies with
+    // a lifetime are visual effects (exhaust) and never collide.
+    #[serde(default)]
+    pub lifetime: Option<u64>,
+}
+
+fn default_record_trail() -> bool {
+    true
+}
+
+fn default_trail_max() -> usize {
+    MAX_TRAIL_POINTS
 }
 
 fn default_fuel() -> f64 {
@@ -139,6 +458,8 @@ fn default_fuel() -> f64 {
 }
 
 impl CelestialBody {
+    // The type defaults from `is_fixed` (fixed bodies are stars); use
+    // `with_type` for a free star, a pinned planet or a spacecraft
     pub fn new(
         id: u32,
         name: &str,
@@ -165,18 +486,168 @@ impl CelestialBody {
             thrust: Vec3::zero(),
             fuel: 100.0,
             max_fuel: 100.0,
+            angular_velocity: Vec3::zero(),
+            orientation: Quat::identity(),
+            is_test_particle: false,
+            hidden: false,
+            record_trail_enabled: true,
+            trail_max: MAX_TRAIL_POINTS,
+            nongrav: None,
+            group: None,
+            no_merge: false,
+            lifetime: None,
+        }
+    }
+
+    pub fn with_type(mut self, body_type: BodyType) -> Self {
+        self.body_type = body_type;
+        self
+    }
+
+    // Spins the body at its constant angular velocity for dt. Uses the exact
+    // axis-angle increment so the quaternion stays unit length over long runs.
+    pub fn advance_orientation(&mut self, dt: f64) {
+        let rate = self.angular_velocity.magnitude();
+        if rate == 0.0 {
+            return;
         }
+        let spin = Quat::from_axis_angle(&self.angular_velocity, rate * dt);
+        self.orientation = spin.mul(&self.orientation).normalize();
+    }
+
+    // Moment of inertia of a uniform sphere
+    pub fn sphere_inertia(mass: f64, radius: f64) -> f64 {
+        0.4 * mass * radius * radius
     }
 
-    pub fn record_trail(&mut self) {
+    pub fn spin_angular_momentum(&self) -> Vec3 {
+        self.angular_velocity.scale(Self::sphere_inertia(self.mass, self.radius))
+    }
+
+    // Acceleration produced by the engine right now: zero unless this is a
+    // spacecraft with fuel left and a non-trivial thrust set
+    pub fn thrust_acceleration(&self) -> Vec3 {
+        if self.body_type == BodyType::Spacecraft && self.fuel > 0.0 {
+            let thrust_mag = self.thrust.magnitude();
+            if thrust_mag > 0.001 {
+                return self.thrust.scale(1.0 / self.mass);
+            }
+        }
+        Vec3::zero()
+    }
+
+    // Adds an impulsive velocity change, paid for at the same fuel rate as
+    // continuous thrust. A short tank delivers only the part it can afford;
+    // like thrust, it does nothing for non-spacecraft or an empty tank.
+    pub fn apply_maneuver(&mut self, delta_v: Vec3) {
+        if self.body_type != BodyType::Spacecraft || self.fuel <= 0.0 {
+            return;
+        }
+        let cost = self.mass.abs() * delta_v.magnitude() * FUEL_PER_IMPULSE;
+        let fraction = if cost > self.fuel { self.fuel / cost } else { 1.0 };
+        self.velocity += delta_v.scale(fraction);
+        self.fuel = (self.fuel - cost).max(0.0);
+    }
+
+    // Outgassing push away from a star at `star`; zero for bodies without a
+    // non-gravitational term
+    pub fn nongrav_acceleration(&self, star: Vec3) -> Vec3 {
+        let Some(strength) = self.nongrav else {
+            return Vec3::zero();
+        };
+        let offset = self.position - star;
+        let dist_sq = offset.dot(&offset);
+        if dist_sq < 1e-10 {
+            return Vec3::zero();
+        }
+        offset.normalize().scale(strength / dist_sq)
+    }
+
+    // Mass this body contributes as a gravity source
+    pub fn source_mass(&self) -> f64 {
+        if self.is_test_particle {
+            0.0
+        } else {
+            self.mass
+        }
+    }
+
+    // Moves the body and its trail by the same offset
+    pub fn translate(&mut self, offset: Vec3) {
+        self.position += offset;
+        for point in self.trail.iter_mut() {
+            point.x += offset.x;
+            point.y += offset.y;
+            point.z += offset.z;
+        }
+    }
+
+    // Rigidly rotates the body's state (trail, spin and thrust included)
+    // about `center` by `angle` radians around the unit vector `axis`
+    pub fn rotate_about(&mut self, center: Vec3, axis: &Vec3, angle: f64) {
+        self.position = center + (self.position - center).rotate(axis, angle);
+        self.velocity = self.velocity.rotate(axis, angle);
+        self.thrust = self.thrust.rotate(axis, angle);
+        self.angular_velocity = self.angular_velocity.rotate(axis, angle);
+        self.orientation = Quat::from_axis_angle(axis, angle).mul(&self.orientation).normalize();
+        for point in self.trail.iter_mut() {
+            let p = center + (Vec3::new(point.x, point.y, point.z) - center).rotate(axis, angle);
+            point.x = p.x;
+            point.y = p.y;
+            point.z = p.z;
+        }
+    }
+
+    // Stores the position and speed relative to a frame at `origin` moving at
+    // `frame_velocity` (both zero for world coordinates)
+    pub fn record_trail(&mut self, time: f64, origin: Vec3, frame_velocity: Vec3) {
+        let position = self.position - origin;
         self.trail.push_back(TrailPoint {
-            x: self.position.x,
-            y: self.position.y,
-            z: self.position.z,
-            speed: self.velocity.magnitude(),
+            x: position.x,
+            y: position.y,
+            z: position.z,
+            speed: (self.velocity - frame_velocity).magnitude(),
+            time,
+            accel: self.acceleration.magnitude(),
         });
-        if self.trail.len() > MAX_TRAIL_POINTS {
+        // A loop rather than a single pop, since trail_max can shrink
+        while self.trail.len() > self.trail_max {
             self.trail.pop_front();
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const H: f64 = 10.0;
+
+    #[test]
+    fn spline_is_newtonian_outside_softening_length() {
+        for r in [H, 1.5 * H, 4.0 * H, 100.0 * H] {
+            let spline = SofteningKernel::Spline.inv_r3(r * r, H * H);
+            let newton = 1.0 / (r * r * r);
+            assert!((spline - newton).abs() <= 1e-12 * newton, "r = {}", r);
+        }
+    }
+
+    #[test]
+    fn spline_matches_plummer_far_out() {
+        for r in [50.0 * H, 100.0 * H, 1000.0 * H] {
+            let spline = SofteningKernel::Spline.inv_r3(r * r, H * H);
+            let plummer = SofteningKernel::Plummer.inv_r3(r * r, H * H);
+            // Plummer is off by about 1.5 (h/r)^2
+            let tolerance = 2.0 * (H / r).powi(2);
+            assert!((spline - plummer).abs() <= tolerance * spline, "r = {}", r);
+        }
+    }
+
+    #[test]
+    fn spline_is_continuous_and_finite_inside() {
+        let at = |r: f64| SofteningKernel::Spline.inv_r3(r * r, H * H);
+        assert!((at(H * (1.0 - 1e-9)) - at(H)).abs() <= 1e-6 * at(H));
+        assert!((at(0.5 * H * (1.0 - 1e-9)) - at(0.5 * H * (1.0 + 1e-9))).abs() <= 1e-6 * at(H));
+        assert!(at(0.0).is_finite());
+    }
+}
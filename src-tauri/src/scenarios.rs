@@ -1,6 +1,9 @@
-use crate::physics::{CelestialBody, Vec3};
-use crate::simulation::SimulationState;
+use crate::galaxy;
+use crate::physics::{BodyType, CelestialBody, Vec3};
+use crate::simulation::{Integrator, SimulationState};
 use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
 
 fn add_planet(
     state: &mut SimulationState,
@@ -75,8 +78,28 @@ fn add_sun(state: &mut SimulationState, mass: f64, radius: f64) {
     state.bodies.push(sun);
 }
 
+// Shifts the free bodies into the zero-momentum frame so hand-tuned initial
+// conditions don't leave the whole system drifting across the screen.
+pub fn zero_net_momentum(state: &mut SimulationState) {
+    let momentum = state.total_momentum();
+    let free_mass: f64 = state.bodies.iter().filter(|b| !b.is_fixed).map(|b| b.mass).sum();
+    if free_mass <= 0.0 {
+        return;
+    }
+
+    let drift = momentum.scale(1.0 / free_mass);
+    for body in state.bodies.iter_mut() {
+        if !body.is_fixed {
+            body.velocity = body.velocity - drift;
+        }
+    }
+}
+
 pub fn load_sun_earth(state: &mut SimulationState) {
     state.clear();
+    // Two bodies always take the brute-force path, so theta is moot; Verlet
+    // with the stock softening keeps the orbit closed indefinitely.
+    state.apply_scenario_settings(0.5, Integrator::Verlet, 10.0);
 
     let sun_mass: f64 = 50000.0;
     let orbit_radius: f64 = 250.0;
@@ -113,6 +136,8 @@ pub fn load_sun_earth(state: &mut SimulationState) {
 
 pub fn load_inner_solar(state: &mut SimulationState) {
     state.clear();
+    // Few bodies, well separated: stock settings
+    state.apply_scenario_settings(0.5, Integrator::Verlet, 10.0);
 
     let sun_mass = 50000.0;
     add_sun(state, sun_mass, 20.0);
@@ -129,6 +154,7 @@ pub fn load_inner_solar(state: &mut SimulationState) {
 
 pub fn load_outer_solar(state: &mut SimulationState) {
     state.clear();
+    state.apply_scenario_settings(0.5, Integrator::Verlet, 10.0);
 
     let sun_mass = 50000.0;
     add_sun(state, sun_mass, 20.0);
@@ -144,6 +170,7 @@ pub fn load_outer_solar(state: &mut SimulationState) {
 
 pub fn load_full_solar(state: &mut SimulationState) {
     state.clear();
+    state.apply_scenario_settings(0.5, Integrator::Verlet, 10.0);
 
     let sun_mass = 50000.0;
     add_sun(state, sun_mass, 20.0);
@@ -162,6 +189,7 @@ pub fn load_full_solar(state: &mut SimulationState) {
 
 pub fn load_binary_star(state: &mut SimulationState) {
     state.clear();
+    state.apply_scenario_settings(0.5, Integrator::Verlet, 10.0);
 
     // Two equal-mass stars orbiting their barycenter
     let star_mass = 25000.0;
@@ -179,7 +207,8 @@ pub fn load_binary_star(state: &mut SimulationState) {
         18.0,
         "#FFD700",
         false,
-    );
+    )
+    .with_type(BodyType::Star);
 
     let id2 = state.allocate_id();
     let star2 = CelestialBody::new(
@@ -191,7 +220,8 @@ pub fn load_binary_star(state: &mut SimulationState) {
         18.0,
         "#FF6B35",
         false,
-    );
+    )
+    .with_type(BodyType::Star);
 
     // Test particle in a distant orbit around the pair
     let test_r = 600.0;
@@ -211,11 +241,94 @@ pub fn load_binary_star(state: &mut SimulationState) {
     state.bodies.push(star1);
     state.bodies.push(star2);
     state.bodies.push(test_particle);
+    zero_net_momentum(state);
+    state.prime_accelerations();
+}
+
+// Pluto-Charon analogue: two comparable bodies circling their barycenter,
+// two small moons circling the pair, and the whole system on a wide orbit
+// about a star. Each level gets the circular speed about its own barycenter.
+pub fn load_binary_planet(state: &mut SimulationState) {
+    state.clear();
+    // The pair sits 16 apart, so the usual softening of 10 would blur it
+    state.apply_scenario_settings(0.5, Integrator::Verlet, 1.0);
+
+    let star_mass = 50000.0;
+    let (primary_mass, secondary_mass) = (300.0, 150.0);
+    let pair_mass = primary_mass + secondary_mass;
+    let moon_mass = 0.01;
+
+    // The pair's Hill radius is about 170 at this distance; the moons stay
+    // well inside it and far enough out (~3x the pair's separation) for
+    // their circumbinary orbits to hold
+    let system_r = 1200.0;
+    let separation = 16.0;
+    let moons = [("Nix", 45.0, 0.0), ("Hydra", 62.0, std::f64::consts::PI * 0.6)];
+
+    let system_mass = pair_mass + moon_mass * moons.len() as f64;
+    let center = Vec3::new(system_r, 0.0, 0.0);
+    let center_vel = Vec3::new(0.0, (state.g * (star_mass + system_mass) / system_r).sqrt(), 0.0);
+
+    let id = state.allocate_id();
+    let star = CelestialBody::new(
+        id,
+        "Sun",
+        Vec3::zero(),
+        Vec3::zero(),
+        star_mass,
+        20.0,
+        "#FFD700",
+        false,
+    )
+    .with_type(BodyType::Star);
+    state.bodies.push(star);
+
+    // Each partner's distance and speed about the pair's barycenter are
+    // shared out inversely to its mass
+    let pair_v = (state.g * pair_mass / separation).sqrt();
+    let partners = [
+        ("Pluto", primary_mass, -secondary_mass / pair_mass, 6.0, "#D9C3A5"),
+        ("Charon", secondary_mass, primary_mass / pair_mass, 4.5, "#9A9A9A"),
+    ];
+    for (name, mass, share, radius, color) in partners {
+        let id = state.allocate_id();
+        state.bodies.push(CelestialBody::new(
+            id,
+            name,
+            center + Vec3::new(separation * share, 0.0, 0.0),
+            center_vel + Vec3::new(0.0, pair_v * share, 0.0),
+            mass,
+            radius,
+            color,
+            false,
+        ));
+    }
+
+    for (name, r, angle) in moons {
+        let v = (state.g * pair_mass / r).sqrt();
+        let (sin, cos) = f64::sin_cos(angle);
+        let id = state.allocate_id();
+        state.bodies.push(CelestialBody::new(
+            id,
+            name,
+            center + Vec3::new(r * cos, r * sin, 0.0),
+            center_vel + Vec3::new(-v * sin, v * cos, 0.0),
+            moon_mass,
+            1.5,
+            "#C8C8C8",
+            false,
+        ));
+    }
+
+    zero_net_momentum(state);
     state.prime_accelerations();
 }
 
 pub fn load_figure_eight(state: &mut SimulationState) {
     state.clear();
+    // The choreography is unstable: small theta, RK4 and a softening well
+    // below the closest approach (~70 units) keep the bodies on the loop.
+    state.apply_scenario_settings(0.1, Integrator::Rk4, 1.0);
 
     // Chenciner-Montgomery three-body figure-8 solution
     // Scaled to our G=100 system
@@ -274,11 +387,13 @@ pub fn load_figure_eight(state: &mut SimulationState) {
     state.bodies.push(b1);
     state.bodies.push(b2);
     state.bodies.push(b3);
+    zero_net_momentum(state);
     state.prime_accelerations();
 }
 
 pub fn load_inclined_solar(state: &mut SimulationState) {
     state.clear();
+    state.apply_scenario_settings(0.5, Integrator::Verlet, 10.0);
 
     let sun_mass = 50000.0;
     add_sun(state, sun_mass, 20.0);
@@ -299,6 +414,9 @@ pub fn load_inclined_solar(state: &mut SimulationState) {
 
 pub fn load_solar_with_belt(state: &mut SimulationState) {
     state.clear();
+    // ~200 asteroids push this onto the tree; the belt is nearly massless so
+    // a looser theta costs no visible accuracy.
+    state.apply_scenario_settings(0.7, Integrator::Verlet, 10.0);
 
     let sun_mass = 50000.0;
     add_sun(state, sun_mass, 20.0);
@@ -313,35 +431,389 @@ pub fn load_solar_with_belt(state: &mut SimulationState) {
     let inner_radius = 380.0;
     let outer_radius = 460.0;
     let count = 200;
-    let mut rng = rand::rng();
+    state.with_rng(|state, rng| {
+        for i in 0..count {
+            let r = inner_radius + rng.random::<f64>() * (outer_radius - inner_radius);
+            let angle = rng.random::<f64>() * std::f64::consts::TAU;
+            let v = (state.g * sun_mass / r).sqrt();
+            let v_perturb = 1.0 + (rng.random::<f64>() - 0.5) * 0.02;
+            let incl = (rng.random::<f64>() - 0.5) * 0.1;
+
+            let id = state.allocate_id();
+            let body = CelestialBody::new(
+                id,
+                &format!("Asteroid {}", i),
+                Vec3::new(r * angle.cos(), r * angle.sin(), 0.0),
+                Vec3::new(
+                    -v * angle.sin() * v_perturb,
+                    v * angle.cos() * v_perturb * incl.cos(),
+                    v * incl.sin() * v_perturb,
+                ),
+                0.001,
+                1.0,
+                "#888888",
+                false,
+            );
+            state.bodies.push(body);
+        }
+    });
+
+    // Jupiter beyond the belt
+    add_planet(state, "Jupiter", 500.0, 317.8, 14.0, "#C88B3A", sun_mass);
 
-    for i in 0..count {
-        let r = inner_radius + rng.random::<f64>() * (outer_radius - inner_radius);
-        let angle = rng.random::<f64>() * std::f64::consts::TAU;
-        let v = (state.g * sun_mass / r).sqrt();
-        let v_perturb = 1.0 + (rng.random::<f64>() - 0.5) * 0.02;
-        let incl = (rng.random::<f64>() - 0.5) * 0.1;
+    state.prime_accelerations();
+}
 
+pub fn load_resonant_belt(state: &mut SimulationState) {
+    state.clear();
+    // Long-run scenario: tight softening so the Sun's and Jupiter's pull on
+    // the belt is essentially Newtonian and the resonances sit where expected
+    state.apply_scenario_settings(0.5, Integrator::Verlet, 1.0);
+
+    let sun_mass = 50000.0;
+    add_sun(state, sun_mass, 20.0);
+
+    // Jupiter is ~20x heavier relative to the Sun than the real one so the
+    // gaps clear in minutes rather than hours of wall-clock time
+    let jupiter_radius = 500.0;
+    add_planet(state, "Jupiter", jupiter_radius, 1000.0, 14.0, "#C88B3A", sun_mass);
+
+    // The 3:1 and 2:1 mean-motion resonances sit at a = a_J * (1/3)^(2/3)
+    // (~240) and a_J * (1/2)^(2/3) (~315). Spread the belt evenly across both.
+    let inner_radius = 200.0;
+    let outer_radius = 360.0;
+    let count = 400;
+    state.with_rng(|state, rng| {
+        for i in 0..count {
+            let r = inner_radius + rng.random::<f64>() * (outer_radius - inner_radius);
+            let angle = rng.random::<f64>() * std::f64::consts::TAU;
+            let v = (state.g * sun_mass / r).sqrt();
+            let incl = (rng.random::<f64>() - 0.5) * 0.05;
+
+            let id = state.allocate_id();
+            let mut body = CelestialBody::new(
+                id,
+                &format!("Asteroid {}", i),
+                Vec3::new(r * angle.cos(), r * angle.sin(), 0.0),
+                Vec3::new(
+                    -v * angle.sin(),
+                    v * angle.cos() * incl.cos(),
+                    v * incl.sin(),
+                ),
+                0.001,
+                1.0,
+                "#888888",
+                false,
+            );
+            // Test particles still feel both the Sun and Jupiter; they just don't
+            // pull on each other, which is what keeps 400 of them cheap
+            body.is_test_particle = true;
+            state.bodies.push(body);
+        }
+    });
+
+    state.prime_accelerations();
+}
+
+fn load_galaxy_collision(state: &mut SimulationState) {
+    galaxy::generate_collision(state, 300, false, None, None, &mut |_, _| {})
+}
+
+// A built-in preset as the scenario picker lists it. `body_count` is what
+// the preset creates under the default body cap.
+#[derive(Debug, Clone, Serialize)]
+pub struct ScenarioInfo {
+    pub id: &'static str,
+    pub name: &'static str,
+    pub description: &'static str,
+    pub body_count: usize,
+    // Every body starts in one plane
+    pub planar: bool,
+    #[serde(skip)]
+    load: fn(&mut SimulationState),
+}
+
+// The one list of presets: `load_by_name`, the picker and the reserved
+// save names all read it
+pub const BUILTIN_SCENARIOS: &[ScenarioInfo] = &[
+    ScenarioInfo {
+        id: "sun_earth",
+        name: "Sun & Earth",
+        description: "Simple two-body orbit",
+        body_count: 2,
+        planar: true,
+        load: load_sun_earth,
+    },
+    ScenarioInfo {
+        id: "inner_solar",
+        name: "Inner Solar System",
+        description: "Sun + Mercury, Venus, Earth, Mars",
+        body_count: 5,
+        planar: true,
+        load: load_inner_solar,
+    },
+    ScenarioInfo {
+        id: "outer_solar",
+        name: "Outer Solar System",
+        description: "Sun + Jupiter, Saturn, Uranus, Neptune",
+        body_count: 5,
+        planar: true,
+        load: load_outer_solar,
+    },
+    ScenarioInfo {
+        id: "full_solar",
+        name: "Full Solar System",
+        description: "Sun + all 8 planets",
+        body_count: 9,
+        planar: true,
+        load: load_full_solar,
+    },
+    ScenarioInfo {
+        id: "binary_star",
+        name: "Binary Star",
+        description: "Two stars orbiting their barycenter",
+        body_count: 3,
+        planar: true,
+        load: load_binary_star,
+    },
+    ScenarioInfo {
+        id: "binary_planet",
+        name: "Binary Planet",
+        description: "Pluto-Charon style pair with two circumbinary moons, orbiting a star",
+        body_count: 5,
+        planar: true,
+        load: load_binary_planet,
+    },
+    ScenarioInfo {
+        id: "figure_eight",
+        name: "Figure-8",
+        description: "Three-body periodic figure-8 solution",
+        body_count: 3,
+        planar: true,
+        load: load_figure_eight,
+    },
+    ScenarioInfo {
+        id: "inclined_solar",
+        name: "Inclined Solar",
+        description: "Full solar system with 3D orbital inclinations",
+        body_count: 9,
+        planar: false,
+        load: load_inclined_solar,
+    },
+    ScenarioInfo {
+        id: "asteroid_belt",
+        name: "Asteroid Belt",
+        description: "Inner solar system with 200 asteroids",
+        body_count: 206,
+        planar: false,
+        load: load_solar_with_belt,
+    },
+    ScenarioInfo {
+        id: "resonant_belt",
+        name: "Kirkwood Gaps",
+        description: "Jupiter clearing 3:1 and 2:1 resonance gaps in a 400-asteroid belt",
+        body_count: 402,
+        planar: false,
+        load: load_resonant_belt,
+    },
+    ScenarioInfo {
+        id: "galaxy_collision",
+        name: "Galaxy Collision",
+        description: "Two galaxies colliding (600 particles)",
+        body_count: 602,
+        planar: false,
+        load: load_galaxy_collision,
+    },
+];
+
+// Loads a preset by the name the frontend uses. Returns false (leaving the
+// state untouched) for unknown names.
+pub fn load_by_name(state: &mut SimulationState, name: &str) -> bool {
+    match BUILTIN_SCENARIOS.iter().find(|s| s.id == name) {
+        Some(scenario) => {
+            (scenario.load)(state);
+            true
+        }
+        None => false,
+    }
+}
+
+// On-disk form of a user scenario, stored as `<dir>/<name>.json`: the
+// settings a built-in loader would pick plus the bodies. Trails are dropped
+// and accelerations are recomputed on load.
+#[derive(Serialize, Deserialize)]
+pub struct SavedScenario {
+    pub name: String,
+    pub dt: f64,
+    pub g: f64,
+    pub softening: f64,
+    pub theta: f64,
+    pub integrator: Integrator,
+    pub bodies: Vec<CelestialBody>,
+}
+
+// Names become file names, so only plain identifiers are accepted
+fn scenario_path(dir: &Path, name: &str) -> Result<PathBuf, String> {
+    let valid = !name.is_empty()
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    if !valid {
+        return Err(format!(
+            "Scenario name '{}' may only use letters, digits, '_' and '-'",
+            name
+        ));
+    }
+    Ok(dir.join(format!("{}.json", name)))
+}
+
+// Built-in names are refused so a save can't silently shadow a preset
+pub fn check_save_name(name: &str) -> Result<(), String> {
+    if BUILTIN_SCENARIOS.iter().any(|s| s.id == name) {
+        return Err(format!("'{}' is a built-in scenario", name));
+    }
+    scenario_path(Path::new(""), name).map(|_| ())
+}
+
+// Overwrites an earlier save of the same name. Past `check_save_name`, every
+// error is a failure to write the file.
+pub fn save_user(state: &SimulationState, dir: &Path, name: &str) -> Result<PathBuf, String> {
+    check_save_name(name)?;
+    let path = scenario_path(dir, name)?;
+    let bodies = state
+        .bodies
+        .iter()
+        .map(|body| {
+            let mut body = body.clone();
+            body.trail.clear();
+            body.acceleration = Vec3::zero();
+            body
+        })
+        .collect();
+    let saved = SavedScenario {
+        name: name.to_string(),
+        dt: state.dt,
+        g: state.g,
+        softening: state.softening,
+        theta: state.theta,
+        integrator: state.integrator,
+        bodies,
+    };
+    let json = serde_json::to_string_pretty(&saved).map_err(|e| e.to_string())?;
+    std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    std::fs::write(&path, json).map_err(|e| e.to_string())?;
+    Ok(path)
+}
+
+// Ok(false) when there is no saved scenario by that name, leaving the state
+// untouched so the caller can fall back to the built-ins. The file is
+// checked against the setters' limits before it replaces anything, so a bad
+// one leaves the current scene in place.
+pub fn load_user(state: &mut SimulationState, dir: &Path, name: &str) -> Result<bool, String> {
+    let path = scenario_path(dir, name)?;
+    let json = match std::fs::read_to_string(&path) {
+        Ok(json) => json,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(false),
+        Err(e) => return Err(e.to_string()),
+    };
+    let saved: SavedScenario = serde_json::from_str(&json).map_err(|e| e.to_string())?;
+
+    let mut staged = state.staging_state();
+    staged.apply_scenario_settings(saved.theta, saved.integrator, saved.softening);
+    staged.dt = saved.dt;
+    staged.g = saved.g;
+    staged
+        .check_settings()
+        .map_err(|e| format!("{}: {}", path.display(), e))?;
+    staged.bodies = saved.bodies;
+    staged.validate_and_repair();
+    state.install_staged(staged);
+    Ok(true)
+}
+
+// Builds a preset on the side and adds its bodies to the current scene
+// without clearing it. The live G, softening and integrator are kept.
+// Returns None for unknown names.
+pub fn append_by_name(state: &mut SimulationState, name: &str, offset: Vec3) -> Option<Vec<u32>> {
+    let mut staged = state.staging_state();
+    if !load_by_name(&mut staged, name) {
+        return None;
+    }
+    state.rng = staged.rng;
+    Some(state.append_bodies(staged.bodies, offset))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_at_rest(state: &SimulationState) {
+        let p = state.bodies.iter().fold(Vec3::zero(), |p, b| p + b.velocity.scale(b.mass));
+        // Relative to the momentum the bodies carry individually
+        let scale: f64 = state.bodies.iter().map(|b| b.velocity.scale(b.mass).magnitude()).sum();
+        assert!(p.magnitude() <= 1e-12 * scale.max(1.0), "net momentum {:?}", p);
+    }
+
+    #[test]
+    fn zero_net_momentum_removes_drift() {
+        let mut state = SimulationState::new();
+        for (i, (mass, vx, vy)) in [(5.0, 3.0, -1.0), (2.0, -0.5, 4.0), (9.0, 1.5, 2.5)]
+            .into_iter()
+            .enumerate()
+        {
+            let id = state.allocate_id();
+            let position = Vec3::new(100.0 * i as f64, 0.0, 0.0);
+            let velocity = Vec3::new(vx, vy, 0.25);
+            let body = CelestialBody::new(id, "b", position, velocity, mass, 1.0, "#fff", false);
+            state.bodies.push(body);
+        }
+        zero_net_momentum(&mut state);
+        assert_at_rest(&state);
+    }
+
+    #[test]
+    fn zero_net_momentum_leaves_fixed_bodies_alone() {
+        let mut state = SimulationState::new();
         let id = state.allocate_id();
-        let body = CelestialBody::new(
-            id,
-            &format!("Asteroid {}", i),
-            Vec3::new(r * angle.cos(), r * angle.sin(), 0.0),
-            Vec3::new(
-                -v * angle.sin() * v_perturb,
-                v * angle.cos() * v_perturb * incl.cos(),
-                v * incl.sin() * v_perturb,
-            ),
-            0.001,
-            1.0,
-            "#888888",
-            false,
-        );
-        state.bodies.push(body);
+        let anchor =
+            CelestialBody::new(id, "a", Vec3::zero(), Vec3::zero(), 100.0, 5.0, "#fff", true);
+        state.bodies.push(anchor);
+        let id = state.allocate_id();
+        let position = Vec3::new(50.0, 0.0, 0.0);
+        let velocity = Vec3::new(0.0, 7.0, 0.0);
+        let moon = CelestialBody::new(id, "m", position, velocity, 1.0, 1.0, "#fff", false);
+        state.bodies.push(moon);
+        zero_net_momentum(&mut state);
+        assert_at_rest(&state);
+        assert_eq!(state.bodies[0].velocity.magnitude(), 0.0);
     }
 
-    // Jupiter beyond the belt
-    add_planet(state, "Jupiter", 500.0, 317.8, 14.0, "#C88B3A", sun_mass);
+    #[test]
+    fn binary_star_and_figure_eight_start_at_rest() {
+        let mut state = SimulationState::new();
+        load_binary_star(&mut state);
+        assert_at_rest(&state);
+        load_figure_eight(&mut state);
+        assert_at_rest(&state);
+    }
 
-    state.prime_accelerations();
+    #[test]
+    fn bad_user_scenario_leaves_the_scene_alone() {
+        let dir = std::env::temp_dir().join(format!("orbitforge-{}", std::process::id()));
+        let mut saved = SimulationState::new();
+        load_by_name(&mut saved, "binary_star");
+        save_user(&saved, &dir, "edited").unwrap();
+        let path = dir.join("edited.json");
+        let json = std::fs::read_to_string(&path).unwrap();
+        let mut edited: serde_json::Value = serde_json::from_str(&json).unwrap();
+        edited["dt"] = serde_json::json!(0.0);
+        std::fs::write(&path, edited.to_string()).unwrap();
+
+        let mut state = SimulationState::new();
+        load_by_name(&mut state, "sun_earth");
+        let bodies = state.bodies.len();
+        let loaded = load_user(&mut state, &dir, "edited");
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(loaded.is_err());
+        assert_eq!(state.bodies.len(), bodies);
+        assert!(state.dt > 0.0);
+    }
 }
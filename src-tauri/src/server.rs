@@ -0,0 +1,117 @@
+// Optional control server (cargo feature "server") for driving the simulation
+// from external scripts. It speaks JSON over WebSocket and shares the same
+// state the Tauri commands lock, so both can be used at once.
+//
+// Each text message is one request tagged by `cmd`, with the same argument
+// names as the matching Tauri command:
+//   {"cmd": "load_scenario", "name": "inner_solar"}
+//   {"cmd": "add_body", "body_data": {...BodyData...}}
+//   {"cmd": "remove_body", "id": 3}
+//   {"cmd": "get_frame"}
+//   {"cmd": "set_speed", "multiplier": 2.0}
+// and is answered with {"ok": <result>} or {"error": "<message>"}.
+//
+// Plain HTTP is out of scope: anything that isn't a WebSocket handshake is
+// dropped. At most MAX_CONNECTIONS clients are served at once; further
+// connections are closed straight away until one of them disconnects.
+
+use crate::commands::{self, BodyData, SimState};
+use crate::scenarios;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use tungstenite::Message;
+
+const MAX_CONNECTIONS: usize = 8;
+
+#[derive(Deserialize)]
+#[serde(tag = "cmd", rename_all = "snake_case")]
+enum Request {
+    LoadScenario { name: String },
+    AddBody { body_data: BodyData },
+    RemoveBody { id: u32 },
+    GetFrame,
+    SetSpeed { multiplier: f64 },
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+enum Response {
+    Ok(Value),
+    Error(String),
+}
+
+// Binds `addr` and serves each connection on its own thread, up to
+// MAX_CONNECTIONS at a time. Only the bind can fail; connection errors just
+// end that connection.
+pub fn spawn(state: SimState, addr: &str) -> std::io::Result<()> {
+    let listener = TcpListener::bind(addr)?;
+    let open = Arc::new(AtomicUsize::new(0));
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            if open.fetch_add(1, Ordering::SeqCst) >= MAX_CONNECTIONS {
+                open.fetch_sub(1, Ordering::SeqCst);
+                continue;
+            }
+            let state = state.clone();
+            let open = open.clone();
+            thread::spawn(move || {
+                serve(state, stream);
+                open.fetch_sub(1, Ordering::SeqCst);
+            });
+        }
+    });
+    Ok(())
+}
+
+fn serve(state: SimState, stream: TcpStream) {
+    let Ok(mut socket) = tungstenite::accept(stream) else {
+        return;
+    };
+    while let Ok(message) = socket.read() {
+        let text = match message {
+            Message::Text(text) => text,
+            Message::Close(_) => break,
+            _ => continue,
+        };
+        let response = match serde_json::from_str::<Request>(&text) {
+            Ok(request) => match dispatch(&state, request) {
+                Ok(value) => Response::Ok(value),
+                Err(e) => Response::Error(e),
+            },
+            Err(e) => Response::Error(format!("Bad request: {}", e)),
+        };
+        let Ok(reply) = serde_json::to_string(&response) else {
+            continue;
+        };
+        if socket.send(Message::text(reply)).is_err() {
+            break;
+        }
+    }
+}
+
+fn dispatch(state: &SimState, request: Request) -> Result<Value, String> {
+    let mut sim = state.lock().unwrap();
+    let value = match request {
+        Request::LoadScenario { name } => {
+            if !scenarios::load_by_name(&mut sim, &name) {
+                return Err(format!("Unknown scenario '{}'", name));
+            }
+            serde_json::to_value(sim.scenario_loaded(&name))
+        }
+        Request::AddBody { body_data } => {
+            let id = commands::add_body_from_data(&mut sim, body_data).map_err(|e| e.to_string())?;
+            Ok(Value::from(id))
+        }
+        Request::RemoveBody { id } => {
+            sim.remove_body(id);
+            Ok(Value::Null)
+        }
+        Request::GetFrame => serde_json::to_value(sim.to_frame()),
+        Request::SetSpeed { multiplier } => Ok(Value::from(sim.set_speed_multiplier(multiplier)?)),
+    };
+    value.map_err(|e| e.to_string())
+}
@@ -1,23 +1,200 @@
-use crate::barneshut;
+use crate::barneshut::{self, TreeBounds};
 use crate::gpu_gravity::GpuGravity;
-use crate::physics::{BodyType, CelestialBody, Vec3};
+use crate::physics::{
+    blend_colors, two_body_orbit, BodyType, CelestialBody, SofteningKernel, Vec3, FUEL_PER_IMPULSE,
+    MAX_TRAIL_POINTS,
+};
+use crate::pm;
+use crate::rng::SimRng;
+use rand::Rng;
+use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap, VecDeque};
 use std::sync::Arc;
+use std::time::Instant;
 
+// `kinetic`, `potential` and `total` treat every body alike. The `free_`
+// figures leave out what fixed bodies can't change: their own kinetic energy
+// (they never move, whatever their velocity says) and the potential between
+// two fixed bodies (a constant). With a pinned star, `free_total` is the
+// quantity the integrator should conserve.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EnergyData {
     pub kinetic: f64,
     pub potential: f64,
     pub total: f64,
+    // 2 KE / |PE|: ~1 in virial equilibrium, below 1 collapsing, above 1
+    // dispersing. Zero when there is no potential energy.
+    pub virial_ratio: f64,
+    #[serde(default)]
+    pub free_kinetic: f64,
+    #[serde(default)]
+    pub free_potential: f64,
+    #[serde(default)]
+    pub free_total: f64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SimulationFrame {
     pub bodies: Vec<CelestialBody>,
     pub tick: u64,
+    // Simulated time elapsed since the last clear/load
+    pub sim_time: f64,
     pub paused: bool,
     pub speed_multiplier: f64,
     pub energy: EnergyData,
+    pub ghost: Option<CelestialBody>,
+    // Body the frame is centered on, if any. `frame_origin` is that body's
+    // world position (and `frame_velocity` its velocity when velocities are
+    // made relative too); add them back to recover world coordinates.
+    pub focus_body: Option<u32>,
+    pub frame_origin: Vec3,
+    pub frame_velocity: Vec3,
+    pub telemetry: Telemetry,
+    // Only filled in while `orbit_info` is on
+    pub orbits: Option<Vec<BodyOrbit>>,
+    // Bodies `frame_lod` left out of this frame; they still exist and should
+    // stay where the last frame that had them put them
+    #[serde(default)]
+    pub held: Vec<u32>,
+}
+
+// Bodies farther than `distance` from the focus body (or the barycenter
+// without one) only appear in every `every`th emitted frame
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct FrameLod {
+    pub distance: f64,
+    pub every: u64,
+}
+
+// Frames since the last full one, and what the scene looked like then, so a
+// changed scene gets a full frame straight away
+#[derive(Debug, Clone, Default)]
+struct LodState {
+    frames: u64,
+    key: Option<(usize, u32, Option<u32>)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OrbitKind {
+    Bound,
+    Parabolic,
+    Hyperbolic,
+}
+
+// Two-body orbit of a free body about its dominant attractor
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BodyOrbit {
+    pub body_id: u32,
+    pub primary_id: u32,
+    // v^2/2 - mu/r with mu = G (M + m)
+    pub specific_energy: f64,
+    pub kind: OrbitKind,
+    // None unless bound
+    pub period: Option<f64>,
+}
+
+// Apsidal precession measured from a tracked body's recorded apsides
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PrecessionReport {
+    pub body_id: u32,
+    pub primary_id: u32,
+    // Consecutive same-kind apsis pairs the rate is averaged over
+    pub orbits: usize,
+    // Positive when the apsides advance in the direction of motion
+    pub degrees_per_orbit: f64,
+    // Mean time between same-kind apsides
+    pub period: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ApsisKind {
+    Periapsis,
+    Apoapsis,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Apsis {
+    kind: ApsisKind,
+    // Unit vector from the primary, in the frame of the primary
+    direction: Vec3,
+    sim_time: f64,
+}
+
+// Bodies as they stood at the end of a past tick. Trails are dropped to keep
+// the history small; positions and velocities are exact.
+#[derive(Debug, Clone)]
+struct Snapshot {
+    tick: u64,
+    sim_time: f64,
+    bodies: Vec<CelestialBody>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnapshotInfo {
+    pub tick: u64,
+    pub sim_time: f64,
+    pub body_count: usize,
+}
+
+// Apsis history of one tracked body. Restarts whenever the primary changes.
+#[derive(Debug, Clone, Default)]
+struct ApsisTrack {
+    primary_id: Option<u32>,
+    // Relative position and r . v at the previous step
+    last: Option<(Vec3, f64)>,
+    apses: Vec<Apsis>,
+}
+
+// Planned impulsive burn for orbit prediction: `delta_v` is added to the
+// spacecraft's velocity at sim time `time`
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct ManeuverNode {
+    pub time: f64,
+    pub delta_v: Vec3,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StabilityReport {
+    pub stable: bool,
+    // Relative change in total energy over the trial run
+    pub drift: f64,
+}
+
+// Outcome of `set_dt`. `warning` is set when the requested dt under-resolves
+// the fastest orbit, whether or not it was reduced.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DtReport {
+    pub dt: f64,
+    pub requested: f64,
+    pub shortest_period: Option<f64>,
+    pub warning: Option<String>,
+}
+
+// Outcome of `set_theta`. `brute_force` is set when theta is low enough that
+// the tree is skipped for brute force; `warning` when either way the force
+// computation gets much slower than at the default theta.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ThetaReport {
+    pub theta: f64,
+    pub requested: f64,
+    pub brute_force: bool,
+    pub warning: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IdReassignment {
+    pub old_id: u32,
+    pub new_id: u32,
+    pub name: String,
+}
+
+// What `validate_and_repair` had to fix in a loaded state
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RepairReport {
+    pub reassigned: Vec<IdReassignment>,
+    pub next_id_raised: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -26,42 +203,916 @@ pub struct CollisionEvent {
     pub survivor_id: u32,
     pub position: Vec3,
     pub combined_mass: f64,
+    pub sim_time: f64,
+}
+
+// A body dropped because its state went NaN/Inf
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NumericalErrorEvent {
+    pub body_id: u32,
+    pub name: String,
+    pub tick: u64,
+    pub sim_time: f64,
+}
+
+// What radius-scaled softening, smooth accretion and the post-Newtonian
+// term need to know about each position beyond its mass
+#[derive(Debug, Clone, Copy)]
+pub struct BodyAttributes {
+    pub radius: f64,
+    pub velocity: Vec3,
+}
+
+impl BodyAttributes {
+    pub fn of(body: &CelestialBody) -> Self {
+        Self {
+            radius: body.radius,
+            velocity: body.velocity,
+        }
+    }
+}
+
+// Which force solver ran last
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AccelerationPath {
+    #[default]
+    Brute,
+    Tree,
+    Gpu,
+    Mesh,
+}
+
+// What pulled on a probed body: another body, or (on the tree path) a far
+// node standing in for all the bodies under it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ForceSource {
+    Body { id: u32, name: String },
+    Node { center_of_mass: Vec3, mass: f64, size: f64 },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccelContribution {
+    pub source: ForceSource,
+    pub acceleration: Vec3,
+    pub magnitude: f64,
+}
+
+// The largest gravity terms on one body. `gravity` sums every term, not
+// just the listed ones; `total` is the acceleration the body last stepped
+// with, so `total - gravity` is thrust, outgassing and contact forces.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccelerationBreakdown {
+    pub body_id: u32,
+    // Tree terms only on the tree path; every other path is explained
+    // pairwise, which the GPU matches and the mesh approximates
+    pub path: AccelerationPath,
+    pub total: Vec3,
+    pub gravity: Vec3,
+    pub term_count: usize,
+    pub contributors: Vec<AccelContribution>,
+}
+
+// Force solver selection. `Auto` picks brute force, tree or GPU by body
+// count; `ParticleMesh` always uses the grid solver when the force law allows.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SolverKind {
+    #[default]
+    Auto,
+    ParticleMesh,
+}
+
+// Cost of the most recent step. `compute_ms` sums every force evaluation in
+// the step (substeps and RK4 stages included); tree figures are zero unless
+// the tree ran.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Telemetry {
+    pub compute_ms: f64,
+    pub path: AccelerationPath,
+    pub body_count: usize,
+    pub tree_nodes: usize,
+    pub tree_depth: usize,
+    // A device is attached and allowed; the GPU still only runs above its
+    // body-count threshold
+    pub gpu_active: bool,
+}
+
+// The path the next force evaluation would take, for showing the solver
+// before any frame has been computed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ActiveSolver {
+    pub path: AccelerationPath,
+    pub body_count: usize,
+    // A device was found at startup, whether or not it is enabled
+    pub gpu_initialized: bool,
+}
+
+// Sent once a loader has finished populating and priming the state, so the
+// UI can reset its camera before the first frame of the new system arrives
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScenarioLoadedEvent {
+    pub name: String,
+    pub body_count: usize,
+    pub bounds: CameraBounds,
+}
+
+// Emitted while a large scene is generated off-lock
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GenerationProgress {
+    pub name: String,
+    pub generated: usize,
+    pub total: usize,
+}
+
+// Sphere enclosing every visible body
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct CameraBounds {
+    pub center: Vec3,
+    pub radius: f64,
+}
+
+// A body whose speed exceeded `max_speed` and was scaled back to it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpeedClampedEvent {
+    pub body_id: u32,
+    pub name: String,
+    pub speed: f64,
+    pub max_speed: f64,
+    pub tick: u64,
+    pub sim_time: f64,
+}
+
+// Everything one tick produces: the frame after it and the events raised
+// during it. Returned by `next_frame` for code driving the simulation itself.
+#[derive(Debug, Clone, Serialize)]
+pub struct FrameUpdate {
+    pub frame: SimulationFrame,
+    pub collisions: Vec<CollisionEvent>,
+    pub numerical_errors: Vec<NumericalErrorEvent>,
+    pub speed_clamps: Vec<SpeedClampedEvent>,
+}
+
+// Frame trail points are stored in. `Focus` records them relative to the focus
+// body (world while there is none), so a moon's trail about its planet closes
+// into a loop instead of smearing along the planet's path.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TrailFrame {
+    #[default]
+    World,
+    Focus,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Integrator {
+    #[default]
+    Verlet,
+    Rk4,
+}
+
+// Slowest and fastest trail speed seen since the range was last reset, so
+// the renderer's speed gradient has a window that doesn't shift every frame
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SpeedRange {
+    pub min: f64,
+    pub max: f64,
+}
+
+impl SpeedRange {
+    fn include(range: Option<Self>, speed: f64) -> Option<Self> {
+        Some(match range {
+            Some(r) => Self {
+                min: r.min.min(speed),
+                max: r.max.max(speed),
+            },
+            None => Self {
+                min: speed,
+                max: speed,
+            },
+        })
+    }
+}
+
+// Cumulative merge counters since the current scenario was loaded
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CollisionStats {
+    pub total_collisions: u64,
+    pub mass_accreted: f64,
+    pub bodies_removed: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CollisionMode {
+    // Overlapping bodies combine into one, conserving mass and momentum
+    #[default]
+    Merge,
+    // Overlapping bodies exchange an impulse and stay separate
+    Bounce,
+    // As merge, except that dust inside a heavier body's capture radius is
+    // drawn in gradually instead of in one jump
+    Accretion,
+    // Overlapping bodies are pushed apart by a spring on the penetration
+    // depth, applied as a force rather than resolved after the step
+    Soft,
+}
+
+// Contact settings. `restitution`, `friction` and `resting_speed` only apply
+// in bounce mode, `stiffness` and `contact_damping` only in soft mode.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct CollisionConfig {
+    #[serde(default)]
+    pub mode: CollisionMode,
+    // Fraction of the normal approach speed kept after an impact
+    #[serde(default = "default_restitution")]
+    pub restitution: f64,
+    // Coulomb coefficient: tangential impulse is capped at friction * normal impulse
+    #[serde(default)]
+    pub friction: f64,
+    // Approach speeds below this are treated as resting contact and zeroed
+    // instead of bounced, so bodies settle rather than buzz on a surface
+    #[serde(default = "default_resting_speed")]
+    pub resting_speed: f64,
+    // Accretion mode: reach of a body's pull on dust, in units of its radius
+    #[serde(default = "default_capture_radius")]
+    pub capture_radius: f64,
+    // Accretion mode: fraction of a captured particle's mass drawn in per
+    // unit time, along with its share of the particle's momentum
+    #[serde(default = "default_accretion_rate")]
+    pub accretion_rate: f64,
+    // Merge mode only: let pairs that touch while unbound (fast flybys) pass
+    // through instead of sticking
+    #[serde(default)]
+    pub require_bound_for_merge: bool,
+    // Soft mode spring: force per unit of penetration depth. The contact
+    // oscillates at sqrt(stiffness / reduced mass), and Verlet stays stable
+    // only while dt is well under 2 / that rate, so light bodies or stiff
+    // springs need a smaller dt (or more substeps) to avoid blowing apart.
+    #[serde(default = "default_stiffness")]
+    pub stiffness: f64,
+    // Soft mode: force per unit approach speed, so piles settle instead of
+    // ringing forever
+    #[serde(default = "default_contact_damping")]
+    pub contact_damping: f64,
+}
+
+impl Default for CollisionConfig {
+    fn default() -> Self {
+        Self {
+            mode: CollisionMode::Merge,
+            restitution: default_restitution(),
+            friction: 0.0,
+            resting_speed: default_resting_speed(),
+            capture_radius: default_capture_radius(),
+            accretion_rate: default_accretion_rate(),
+            require_bound_for_merge: false,
+            stiffness: default_stiffness(),
+            contact_damping: default_contact_damping(),
+        }
+    }
+}
+
+impl CollisionConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(0.0..=1.0).contains(&self.restitution) {
+            return Err(format!("restitution must lie in [0, 1], got {}", self.restitution));
+        }
+        if !(self.capture_radius.is_finite() && self.capture_radius >= 1.0) {
+            return Err(format!("capture radius must be at least 1, got {}", self.capture_radius));
+        }
+        let non_negative = [
+            ("friction", self.friction),
+            ("resting speed", self.resting_speed),
+            ("accretion rate", self.accretion_rate),
+            ("stiffness", self.stiffness),
+            ("contact damping", self.contact_damping),
+        ];
+        for (name, value) in non_negative {
+            if !value.is_finite() || value < 0.0 {
+                return Err(format!("{} must be zero or more, got {}", name, value));
+            }
+        }
+        Ok(())
+    }
+}
+
+fn default_stiffness() -> f64 {
+    1000.0
+}
+
+fn default_contact_damping() -> f64 {
+    10.0
+}
+
+fn default_restitution() -> f64 {
+    0.8
+}
+
+fn default_resting_speed() -> f64 {
+    0.5
+}
+
+fn default_capture_radius() -> f64 {
+    2.0
+}
+
+fn default_accretion_rate() -> f64 {
+    1.0
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ColorScheme {
+    // Stars gold, spacecraft white, planets rocky/ocean/gas-giant by mass
+    BodyType,
+    // Blue (lightest) through red (heaviest) on a log-mass scale
+    Mass,
+}
+
+// What happens when an addition would push the body count past `max_bodies`
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OverflowPolicy {
+    // Refuse the bodies that don't fit
+    #[default]
+    Reject,
+    // Make space by removing the least massive non-fixed bodies
+    EvictLightest,
+}
+
+// How the softening length between two bodies is chosen.
+// `Constant` uses the global `softening` everywhere. `RadiusScaled` uses a
+// fraction of the pair's summed radii for direct body-body terms, so small
+// bodies can form tight binaries while tree nodes and the GPU path keep the
+// global value for smooth long-range forces. `Auto` is `Constant` with the
+// value refitted to the scene's scale every time a scene loads, overriding
+// the scene's own softening.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SofteningMode {
+    #[default]
+    Constant,
+    RadiusScaled,
+    Auto,
 }
 
+const RADIUS_SOFTENING_FACTOR: f64 = 0.5;
+
+// Auto softening as a fraction of the mean spacing between bodies; gives
+// about the stock softening of 10 for the sun-earth scene
+const AUTO_SOFTENING_FRACTION: f64 = 0.05;
+
+// Smooth accretion starts softening a pair at this many radius sums apart
+const ACCRETION_RAMP_START: f64 = 3.0;
+
+// Relative energy drift a trial run may show before parameters count as unstable
+const MAX_STABLE_DRIFT: f64 = 0.05;
+
+// Fewest steps per orbit that still trace it; coarser dt shreds orbits
+const MIN_STEPS_PER_PERIOD: f64 = 20.0;
+
+// Below this many bodies the broad-phase hash costs more than it saves
+const HASH_MIN_BODIES: usize = 64;
+
+// In accretion mode a body lighter than this fraction of a neighbour is dust
+// to it
+const DUST_MASS_RATIO: f64 = 0.01;
+
+// Dust is swallowed whole once it is down to this fraction of its accretor
+const DUST_MIN_MASS_RATIO: f64 = 1e-6;
+
+// Exhaust particles shed per thrusting spacecraft per tick
+const EXHAUST_PER_TICK: usize = 2;
+
+// Burning fuel at 0.1 per unit of thrust per time unit makes the effective
+// exhaust speed thrust / flow rate = 10
+const EXHAUST_SPEED: f64 = 10.0;
+
+// Sideways scatter of the plume, as a fraction of the exhaust speed
+const EXHAUST_SPREAD: f64 = 0.15;
+
+// Apsides kept per tracked body; older ones are dropped
+const MAX_APSES: usize = 64;
+
+// Snapshots kept in the history; older ones are dropped
+const MAX_SNAPSHOTS: usize = 64;
+
+// Below this theta the tree opens nearly every node, so brute force gives
+// practically the same sums without the tree's overhead
+const MIN_TREE_THETA: f64 = 0.1;
+
+// Tree runs at a theta under this get a cost estimate in `set_theta`
+const LOW_THETA: f64 = 0.3;
+
+// Bodies sampled for that estimate
+const THETA_SAMPLE_BODIES: usize = 16;
+
+// Ticks between recorded trail points
+const TRAIL_STRIDE: u64 = 2;
+
+// How often period-fitted trails are refitted, in ticks; often enough to
+// follow orbits changed by burns or encounters
+const PERIOD_TRAIL_REFRESH_TICKS: u64 = 120;
+
+// Bounds on a period-fitted trail: enough points for a smooth loop, and a
+// cap so slow outer orbits don't hold thousands of points each
+const MIN_PERIOD_TRAIL: usize = 32;
+const MAX_PERIOD_TRAIL: usize = 3000;
+
+// Steps a rebound has to last before bounce mode resolves it as a bounce
+const RESOLVABLE_BOUNCE_STEPS: f64 = 3.0;
+
+// Id given to the ghost inside prediction clones; never allocated to a real body
+const GHOST_ID: u32 = u32::MAX;
+
 #[derive(Serialize, Deserialize)]
 pub struct SimulationState {
     pub bodies: Vec<CelestialBody>,
     pub tick: u64,
+    #[serde(default)]
+    pub sim_time: f64,
     pub dt: f64,
     pub g: f64,
     pub softening: f64,
     pub paused: bool,
     pub speed_multiplier: f64,
+    // Integration steps per dt. Raises accuracy without changing how fast
+    // simulated time passes.
+    #[serde(default = "default_substeps")]
+    pub substeps: u32,
+    // Fraction of a step owed from earlier ticks
+    #[serde(skip)]
+    step_accumulator: f64,
     pub next_id: u32,
+    // Barnes-Hut opening angle. 0 disables the approximation entirely; below
+    // MIN_TREE_THETA the tree is dropped for brute force.
     #[serde(default = "default_theta")]
     pub theta: f64,
+    // Frozen octree root; None refits it around every body on each build.
+    // Massive bodies outside it are summed directly instead of via the tree.
+    #[serde(default)]
+    pub tree_bounds: Option<TreeBounds>,
+    #[serde(default)]
+    pub integrator: Integrator,
+    #[serde(default)]
+    pub softening_mode: SofteningMode,
+    // Set by `clear`, so the next priming (the end of a load) refits auto
+    // softening to the new scene
+    #[serde(skip)]
+    refit_softening: bool,
+    #[serde(default)]
+    pub softening_kernel: SofteningKernel,
+    // Ramps a pair's softening up to its radius sum as the two close in, so
+    // bodies about to merge drift together instead of slingshotting
+    #[serde(default)]
+    pub smooth_accretion: bool,
+    // Force falls off as 1/r^exponent; only 2.0 is physical
+    #[serde(default = "default_force_exponent")]
+    pub force_exponent: f64,
+    // Toy first-order post-Newtonian term, pairwise, so it runs on direct
+    // summation. `light_speed` is c in simulation units; small values
+    // exaggerate the effect enough to see perihelion precession.
+    #[serde(default)]
+    pub relativistic: bool,
+    #[serde(default = "default_light_speed")]
+    pub light_speed: f64,
+    #[serde(default)]
+    pub collision: CollisionConfig,
+    #[serde(default)]
+    pub collision_stats: CollisionStats,
+    #[serde(default = "default_max_bodies")]
+    pub max_bodies: usize,
+    #[serde(default)]
+    pub overflow_policy: OverflowPolicy,
+    // Non-physical toy mode: lets bodies carry negative (repulsive) mass
+    #[serde(default)]
+    pub allow_negative_mass: bool,
+    // Placement preview. Lives outside `bodies`, so it is never a force source
+    // or a collision participant until committed.
+    #[serde(skip)]
+    pub ghost: Option<CelestialBody>,
+    // Reference-frame body for emitted frames; the simulation itself always
+    // runs in world coordinates
+    #[serde(default)]
+    pub focus_body: Option<u32>,
+    #[serde(default)]
+    pub focus_velocity: bool,
+    #[serde(default)]
+    pub trail_frame: TrailFrame,
+    // Body the stored trail points are relative to; None for world
+    #[serde(default)]
+    trail_origin: Option<u32>,
+    // Bodies as they were before the last `collapse`, so it can be undone
+    #[serde(skip)]
+    collapse_snapshot: Option<Vec<CelestialBody>>,
+    // Ticks between history snapshots; 0 records none
+    #[serde(default)]
+    pub snapshot_interval: u64,
+    #[serde(skip)]
+    snapshots: VecDeque<Snapshot>,
+    // Throttles distant bodies in `next_frame`; None sends every body
+    #[serde(default)]
+    pub frame_lod: Option<FrameLod>,
+    #[serde(skip)]
+    lod: LodState,
+    #[serde(skip)]
+    pub step_hook: Option<StepHook>,
+    // Drop bodies whose state goes non-finite before they reach the force
+    // computation. Turn off to watch NaNs propagate when debugging.
+    #[serde(default = "default_nan_guard")]
+    pub nan_guard: bool,
+    // Size each body's trail to one orbit about its dominant body
+    #[serde(default)]
+    pub period_trails: bool,
+    // Side of a cube centered on the origin whose opposite faces are joined.
+    // Gravity uses the nearest image of each body, which forces the
+    // brute-force path.
+    #[serde(default)]
+    pub periodic: Option<f64>,
+    // Adds per-body orbit energy and period to frames. Off by default since
+    // finding each body's primary is O(n^2).
+    #[serde(default)]
+    pub orbit_info: bool,
+    // Settling drag: for the next `settle_ticks` ticks each free body's
+    // velocity relaxes toward a circular orbit about its primary at rate
+    // `drag` (per unit time), then the drag switches itself off
+    #[serde(default)]
+    pub drag: f64,
+    #[serde(default)]
+    pub settle_ticks: u64,
+    // Thrusting spacecraft shed short-lived exhaust particles. They are test
+    // particles, so they never pull on anything.
+    #[serde(default)]
+    pub spawn_exhaust: bool,
+    // Ticks each exhaust particle lasts
+    #[serde(default = "default_exhaust_lifetime")]
+    pub exhaust_lifetime: u64,
+    // Optional speed cap applied after every substep; None disables it
+    #[serde(default)]
+    pub max_speed: Option<f64>,
+    // Clamps since the caller last drained them, at most one per body per tick
+    #[serde(skip)]
+    pub speed_clamps: Vec<SpeedClampedEvent>,
+    // Shared by every generator; exported so a reloaded run continues the
+    // same random sequence
+    #[serde(default)]
+    pub rng: SimRng,
+    // Bodies removed by the NaN guard since the caller last drained them
+    #[serde(skip)]
+    pub numerical_errors: Vec<NumericalErrorEvent>,
+    #[serde(skip)]
+    pub telemetry: Telemetry,
+    // None until a trail point has been recorded
+    #[serde(skip)]
+    pub speed_range: Option<SpeedRange>,
+    #[serde(skip)]
+    apsis_tracks: HashMap<u32, ApsisTrack>,
+    // Brute force up to `brute_max` bodies, the tree above it, and the GPU
+    // (when active) above `gpu_min`
+    #[serde(default = "default_brute_max")]
+    pub brute_max: usize,
+    #[serde(default = "default_gpu_min")]
+    pub gpu_min: usize,
+    #[serde(default)]
+    pub solver: SolverKind,
+    // Cells per side of the particle-mesh grid
+    #[serde(default = "default_pm_grid")]
+    pub pm_grid: usize,
+    // Recovers most of the f64 position detail on the GPU path, at some cost
+    // in shader time
+    #[serde(default)]
+    pub gpu_precise: bool,
+    // Off forces the CPU paths while keeping the device for re-enabling
+    #[serde(default = "default_use_gpu")]
+    pub use_gpu: bool,
     #[serde(skip)]
     pub gpu: Option<Arc<GpuGravity>>,
+    // Dedicated threads for the parallel physics; None uses rayon's global
+    // pool (every core)
+    #[serde(skip)]
+    pub pool: Option<Arc<rayon::ThreadPool>>,
+    // Frontend camera/view, saved and restored with the state but never read
+    // here
+    #[serde(default)]
+    pub view: serde_json::Value,
 }
 
 fn default_theta() -> f64 {
     0.5
 }
 
+fn default_force_exponent() -> f64 {
+    2.0
+}
+
+// Mercury in the inner solar scenario precesses about a degree per orbit
+fn default_light_speed() -> f64 {
+    5000.0
+}
+
+fn default_max_bodies() -> usize {
+    10_000
+}
+
+fn default_nan_guard() -> bool {
+    true
+}
+
+fn default_brute_max() -> usize {
+    50
+}
+
+fn default_gpu_min() -> usize {
+    500
+}
+
+fn default_pm_grid() -> usize {
+    64
+}
+
+fn default_substeps() -> u32 {
+    1
+}
+
+fn default_exhaust_lifetime() -> u64 {
+    90
+}
+
+pub const MAX_SUBSTEPS: u32 = 64;
+
+// Range of force-law exponents offered; beyond it orbits either barely bend
+// or collapse within a few steps
+pub const FORCE_EXPONENT_RANGE: std::ops::RangeInclusive<f64> = 0.5..=4.0;
+
+fn default_use_gpu() -> bool {
+    true
+}
+
+// Physics threads when nobody has asked for a number: three quarters of the
+// cores, leaving the rest for the UI and whatever else the machine is doing
+pub fn default_physics_threads() -> usize {
+    let cores = std::thread::available_parallelism().map_or(1, |n| n.get());
+    (cores * 3 / 4).max(1)
+}
+
+// Limits shared by every setter and by imports: dt > 0, softening >= 0,
+// theta >= 0, speed > 0, everything finite. Zero softening is allowed;
+// coincident bodies are the NaN guard's problem.
+pub fn check_dt(dt: f64) -> Result<f64, String> {
+    if !dt.is_finite() || dt <= 0.0 {
+        return Err(format!("dt must be positive, got {}", dt));
+    }
+    Ok(dt)
+}
+
+pub fn check_gravity(g: f64) -> Result<f64, String> {
+    if !g.is_finite() {
+        return Err(format!("G must be finite, got {}", g));
+    }
+    Ok(g)
+}
+
+pub fn check_softening(softening: f64) -> Result<f64, String> {
+    if !softening.is_finite() || softening < 0.0 {
+        return Err(format!("softening must be zero or more, got {}", softening));
+    }
+    Ok(softening)
+}
+
+pub fn check_theta(theta: f64) -> Result<f64, String> {
+    if !theta.is_finite() || theta < 0.0 {
+        return Err(format!("theta must be zero or more, got {}", theta));
+    }
+    Ok(theta)
+}
+
+pub fn check_speed(multiplier: f64) -> Result<f64, String> {
+    if !multiplier.is_finite() || multiplier <= 0.0 {
+        return Err(format!("speed multiplier must be positive, got {}", multiplier));
+    }
+    Ok(multiplier)
+}
+
+// Observer run after every tick `step` or `step_once` advances, with the
+// state after it and the tick's collisions. It runs on the thread that
+// stepped (the tick thread in the app) while that thread holds the state, so
+// it should return quickly and must not lock the shared `SimState` itself or
+// it will deadlock. Send + Sync because the state is shared with the physics
+// threads; anything it captures must be too.
+pub type StepHook = Box<dyn FnMut(&SimulationState, &[CollisionEvent]) + Send + Sync>;
+
+// Configures a state from outside the crate (headless runs, scripts, tests)
+// without touching fields directly. Anything not set keeps the `new()`
+// default, and the GPU is never attached.
+pub struct SimulationStateBuilder {
+    state: SimulationState,
+}
+
+impl SimulationStateBuilder {
+    pub fn dt(mut self, dt: f64) -> Self {
+        self.state.dt = dt;
+        self
+    }
+
+    pub fn g(mut self, g: f64) -> Self {
+        self.state.g = g;
+        self
+    }
+
+    pub fn softening(mut self, softening: f64) -> Self {
+        self.state.softening = softening;
+        self
+    }
+
+    pub fn theta(mut self, theta: f64) -> Self {
+        self.state.theta = theta.clamp(0.0, 2.0);
+        self
+    }
+
+    pub fn integrator(mut self, integrator: Integrator) -> Self {
+        self.state.integrator = integrator;
+        self
+    }
+
+    pub fn softening_mode(mut self, mode: SofteningMode) -> Self {
+        self.state.softening_mode = mode;
+        self
+    }
+
+    pub fn collision(mut self, config: CollisionConfig) -> Self {
+        self.state.collision = config;
+        self
+    }
+
+    pub fn max_bodies(mut self, max_bodies: usize) -> Self {
+        self.state.max_bodies = max_bodies.max(1);
+        self
+    }
+
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.state.rng = SimRng::from_seed(seed);
+        self
+    }
+
+    pub fn bodies(mut self, bodies: Vec<CelestialBody>) -> Self {
+        self.state.bodies = bodies;
+        self
+    }
+
+    pub fn on_step(
+        mut self,
+        hook: impl FnMut(&SimulationState, &[CollisionEvent]) + Send + Sync + 'static,
+    ) -> Self {
+        self.state.step_hook = Some(Box::new(hook));
+        self
+    }
+
+    // Checks the settings against the same limits the setters use, then
+    // repairs ids and primes accelerations, so the state is ready to step
+    pub fn build(self) -> Result<SimulationState, String> {
+        let mut state = self.state;
+        state.check_settings()?;
+        state.validate_and_repair();
+        state.prime_accelerations();
+        Ok(state)
+    }
+}
+
+impl Default for SimulationState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl SimulationState {
+    pub fn builder() -> SimulationStateBuilder {
+        SimulationStateBuilder {
+            state: SimulationState::new(),
+        }
+    }
+
     pub fn new() -> Self {
         Self {
             bodies: Vec::new(),
             tick: 0,
+            sim_time: 0.0,
             dt: 0.016,
             g: 100.0,
             softening: 10.0,
             paused: false,
             speed_multiplier: 1.0,
+            step_accumulator: 0.0,
             next_id: 0,
             theta: 0.5,
+            tree_bounds: None,
+            integrator: Integrator::Verlet,
+            softening_mode: SofteningMode::Constant,
+            refit_softening: false,
+            softening_kernel: SofteningKernel::Plummer,
+            smooth_accretion: false,
+            force_exponent: 2.0,
+            relativistic: false,
+            light_speed: default_light_speed(),
+            collision: CollisionConfig::default(),
+            collision_stats: CollisionStats::default(),
+            max_bodies: default_max_bodies(),
+            overflow_policy: OverflowPolicy::Reject,
+            allow_negative_mass: false,
+            ghost: None,
+            focus_body: None,
+            focus_velocity: false,
+            trail_frame: TrailFrame::World,
+            trail_origin: None,
+            collapse_snapshot: None,
+            snapshot_interval: 0,
+            snapshots: VecDeque::new(),
+            frame_lod: None,
+            lod: LodState::default(),
+            step_hook: None,
+            nan_guard: true,
+            period_trails: false,
+            periodic: None,
+            orbit_info: false,
+            drag: 0.0,
+            settle_ticks: 0,
+            spawn_exhaust: false,
+            exhaust_lifetime: default_exhaust_lifetime(),
+            max_speed: None,
+            speed_clamps: Vec::new(),
+            rng: SimRng::default(),
+            numerical_errors: Vec::new(),
+            telemetry: Telemetry::default(),
+            speed_range: None,
+            apsis_tracks: HashMap::new(),
+            brute_max: default_brute_max(),
+            gpu_min: default_gpu_min(),
+            solver: SolverKind::Auto,
+            pm_grid: default_pm_grid(),
+            substeps: default_substeps(),
+            gpu_precise: false,
+            use_gpu: true,
             gpu: None,
+            pool: None,
+            view: serde_json::Value::Null,
+        }
+    }
+
+    // Keeps user-supplied masses away from zero; the sign is only preserved
+    // when negative-mass mode is on
+    pub fn clamp_mass(&self, mass: f64) -> f64 {
+        if self.allow_negative_mass && mass < 0.0 {
+            mass.min(-0.01)
+        } else {
+            mass.max(0.01)
+        }
+    }
+
+    // Makes ids unique and keeps `next_id` strictly above all of them. The
+    // first body holding an id keeps it; later duplicates (e.g. from
+    // hand-edited JSON) get fresh ids so `find_body_mut` can't hit the wrong one.
+    pub fn validate_and_repair(&mut self) -> RepairReport {
+        let mut report = RepairReport::default();
+
+        let max_id = self.bodies.iter().map(|b| b.id).max();
+        if let Some(max_id) = max_id {
+            if self.next_id <= max_id {
+                self.next_id = max_id + 1;
+                report.next_id_raised = true;
+            }
+        }
+
+        let mut seen = std::collections::HashSet::new();
+        for i in 0..self.bodies.len() {
+            let old_id = self.bodies[i].id;
+            if seen.insert(old_id) {
+                continue;
+            }
+            let new_id = self.allocate_id();
+            self.bodies[i].id = new_id;
+            seen.insert(new_id);
+            report.reassigned.push(IdReassignment {
+                old_id,
+                new_id,
+                name: self.bodies[i].name.clone(),
+            });
         }
+
+        report
+    }
+
+    // Lends the shared rng to `f` along with the state it belongs to, so a
+    // generator can draw from it while adding bodies. Generators borrow this
+    // one rather than seeding their own so the sequence carries across loads
+    // and exports.
+    pub fn with_rng<R>(&mut self, f: impl FnOnce(&mut Self, &mut SimRng) -> R) -> R {
+        // Cheap fixed-seed stand-in while the real one is lent out
+        let mut rng = std::mem::replace(&mut self.rng, SimRng::from_seed(0));
+        let result = f(self, &mut rng);
+        self.rng = rng;
+        result
     }
 
     pub fn allocate_id(&mut self) -> u32 {
@@ -73,14 +1124,300 @@ impl SimulationState {
     pub fn add_body(&mut self, body: CelestialBody) -> u32 {
         let id = body.id;
         self.bodies.push(body);
-        self.compute_accelerations();
+        self.refresh_accelerations();
         id
     }
 
-    pub fn remove_body(&mut self, id: u32) {
+    // Frees space for `incoming` new bodies according to the overflow policy
+    // and returns how many of them fit under `max_bodies`. `keep` is never
+    // evicted; pass the body the newcomers will orbit.
+    pub fn make_room(&mut self, incoming: usize, keep: Option<u32>) -> usize {
+        let free = self.max_bodies.saturating_sub(self.bodies.len());
+        if incoming <= free || self.overflow_policy == OverflowPolicy::Reject {
+            return incoming.min(free);
+        }
+
+        let mut candidates: Vec<(f64, u32)> = self
+            .bodies
+            .iter()
+            .filter(|b| !b.is_fixed && Some(b.id) != keep)
+            .map(|b| (b.mass, b.id))
+            .collect();
+        candidates.sort_by(|a, b| a.0.total_cmp(&b.0));
+        let evict: Vec<u32> = candidates
+            .iter()
+            .take(incoming - free)
+            .map(|&(_, id)| id)
+            .collect();
+        self.bodies.retain(|b| !evict.contains(&b.id));
+
+        (free + evict.len()).min(incoming)
+    }
+
+    // Merges another scene's bodies into this one, shifted by `offset` and
+    // given fresh ids, then primes accelerations once. Bodies past the cap are
+    // dropped from the end. Returns the new ids.
+    pub fn append_bodies(&mut self, bodies: Vec<CelestialBody>, offset: Vec3) -> Vec<u32> {
+        let room = self.make_room(bodies.len(), None);
+        let mut ids = Vec::with_capacity(room);
+        for mut body in bodies.into_iter().take(room) {
+            body.id = self.allocate_id();
+            body.translate(offset);
+            ids.push(body.id);
+            self.bodies.push(body);
+        }
+        self.prime_accelerations();
+        ids
+    }
+
+    // Scatters `count` bodies on circular orbits in the xy-plane of body
+    // `center_id`, uniform in area between the two radii and spread over
+    // `thickness` in z, all moving with the center body. Massive particles
+    // still orbit at the center body's speed; their own pull is left out.
+    // Bodies past the cap are dropped. Returns the new ids.
+    pub fn add_disc(
+        &mut self,
+        center_id: u32,
+        inner_r: f64,
+        outer_r: f64,
+        count: usize,
+        thickness: f64,
+        test_particles: bool,
+    ) -> Result<Vec<u32>, String> {
+        let Some(center) = self.find_body(center_id) else {
+            return Err(format!("Body {} not found", center_id));
+        };
+        if !(inner_r.is_finite() && outer_r.is_finite()) || inner_r <= center.radius {
+            return Err(format!(
+                "Inner radius must be finite and outside the body's radius of {}",
+                center.radius
+            ));
+        }
+        if outer_r < inner_r {
+            return Err("Outer radius must not be smaller than the inner radius".to_string());
+        }
+        if !thickness.is_finite() || thickness < 0.0 {
+            return Err(format!("Disc thickness must be non-negative, got {}", thickness));
+        }
+        let (origin, bulk_vel, mass) = (center.position, center.velocity, center.source_mass());
+        let prefix = format!("{} disc ", center.name);
+
+        let room = self.make_room(count, Some(center_id));
+        let ids = self.with_rng(|state, rng| {
+            let mut ids = Vec::with_capacity(room);
+            for i in 0..room {
+                let u: f64 = rng.random();
+                let r = (inner_r * inner_r + u * (outer_r * outer_r - inner_r * inner_r)).sqrt();
+                let angle = rng.random::<f64>() * std::f64::consts::TAU;
+                let z = (rng.random::<f64>() - 0.5) * thickness;
+                let v = (state.g * mass / r).sqrt();
+
+                let id = state.allocate_id();
+                let mut particle = CelestialBody::new(
+                    id,
+                    &format!("{}{}", prefix, i),
+                    origin + Vec3::new(r * angle.cos(), r * angle.sin(), z),
+                    bulk_vel + Vec3::new(-v * angle.sin(), v * angle.cos(), 0.0),
+                    0.01,
+                    0.5,
+                    "#A89F91",
+                    false,
+                );
+                particle.is_test_particle = test_particles;
+                ids.push(id);
+                state.bodies.push(particle);
+            }
+            ids
+        });
+        self.prime_accelerations();
+        Ok(ids)
+    }
+
+    // Overwrites every body's color; only runs on request so user-chosen
+    // colors survive otherwise
+    pub fn auto_color(&mut self, scheme: ColorScheme) {
+        match scheme {
+            ColorScheme::BodyType => {
+                for body in self.bodies.iter_mut() {
+                    let color = match body.body_type {
+                        BodyType::Star => "#FFD700",
+                        BodyType::Spacecraft => "#FFFFFF",
+                        BodyType::Planet if body.mass < 1.0 => "#A89F91",
+                        BodyType::Planet if body.mass < 50.0 => "#4A90D9",
+                        BodyType::Planet => "#C88B3A",
+                    };
+                    body.color = color.to_string();
+                }
+            }
+            ColorScheme::Mass => {
+                let log_mass = |b: &CelestialBody| b.mass.abs().max(1e-12).log10();
+                let (lo, hi) = self.bodies.iter().map(log_mass).fold(
+                    (f64::INFINITY, f64::NEG_INFINITY),
+                    |(lo, hi), m| (lo.min(m), hi.max(m)),
+                );
+                let span = hi - lo;
+                for body in self.bodies.iter_mut() {
+                    let t = if span > 0.0 { (log_mass(body) - lo) / span } else { 0.5 };
+                    let hue = 240.0 * (1.0 - t);
+                    body.color = format!("hsl({:.0}, 80%, 60%)", hue);
+                }
+            }
+        }
+    }
+
+    // Tilts the whole scene about its barycenter; velocities turn with it so
+    // every orbit keeps its shape in the new plane
+    pub fn rotate_system(&mut self, axis: Vec3, angle: f64) {
+        let axis = axis.normalize();
+        if axis.magnitude() == 0.0 || self.bodies.is_empty() {
+            return;
+        }
+        let center = self.barycenter();
+        for body in self.bodies.iter_mut() {
+            body.rotate_about(center, &axis, angle);
+        }
+        self.prime_accelerations();
+    }
+
+    // Flips every velocity and spin. Where gravity depends only on position,
+    // Verlet then retraces the path it came along, up to round-off, provided
+    // nothing collided or burned fuel on the way. The post-Newtonian term,
+    // non-gravitational forces and settling drag depend on velocity or only
+    // ever push one way, so with any of them on the run won't come back;
+    // the returned warning names them.
+    pub fn reverse(&mut self) -> Option<String> {
+        for body in self.bodies.iter_mut() {
+            body.velocity = body.velocity.scale(-1.0);
+            body.angular_velocity = body.angular_velocity.scale(-1.0);
+        }
+        let mut irreversible = Vec::new();
+        if self.relativistic {
+            irreversible.push("post-Newtonian gravity");
+        }
+        if self.bodies.iter().any(|b| b.nongrav.is_some()) {
+            irreversible.push("non-gravitational forces");
+        }
+        if self.settle_ticks > 0 && self.drag > 0.0 {
+            irreversible.push("settling drag");
+        }
+        if irreversible.is_empty() {
+            return None;
+        }
+        Some(format!(
+            "{} will not retrace the path after reversing",
+            irreversible.join(", ")
+        ))
+    }
+
+    // Sum of m v over every body, fixed ones included
+    pub fn total_momentum(&self) -> Vec3 {
+        let mut momentum = Vec3::zero();
+        for body in &self.bodies {
+            momentum += body.velocity.scale(body.mass);
+        }
+        momentum
+    }
+
+    // Center of mass, weighting by |mass| so negative-mass bodies can't push
+    // it to infinity
+    pub fn barycenter(&self) -> Vec3 {
+        let mut weighted = Vec3::zero();
+        let mut total = 0.0;
+        for body in &self.bodies {
+            weighted += body.position.scale(body.mass.abs());
+            total += body.mass.abs();
+        }
+        if total > 0.0 {
+            weighted.scale(1.0 / total)
+        } else {
+            Vec3::zero()
+        }
+    }
+
+    // Replaces every body with one at the barycenter carrying the total mass
+    // and momentum, sized to their summed volume: the system seen from far
+    // away. Returns the new body's id.
+    pub fn collapse(&mut self) -> Result<u32, String> {
+        let total_mass: f64 = self.bodies.iter().map(|b| b.mass).sum();
+        if self.bodies.is_empty() || total_mass <= 0.0 {
+            return Err("Nothing with positive total mass to collapse".to_string());
+        }
+        let velocity = self.total_momentum().scale(1.0 / total_mass);
+        let radius = self.bodies.iter().map(|b| b.radius.powi(3)).sum::<f64>().cbrt();
+        let heaviest = self.bodies.iter().max_by(|a, b| a.mass.total_cmp(&b.mass)).unwrap();
+        let color = heaviest.color.clone();
+        let body_type = heaviest.body_type;
+
+        let id = self.allocate_id();
+        let body = CelestialBody::new(
+            id,
+            "System",
+            self.barycenter(),
+            velocity,
+            total_mass,
+            radius,
+            &color,
+            false,
+        )
+        .with_type(body_type);
+        self.collapse_snapshot = Some(std::mem::replace(&mut self.bodies, vec![body]));
+        self.focus_body = None;
+        self.prime_accelerations();
+        Ok(id)
+    }
+
+    // Puts back the bodies the last `collapse` replaced, as they were then,
+    // in place of whatever is there now. False if there is nothing to restore.
+    pub fn uncollapse(&mut self) -> bool {
+        let Some(bodies) = self.collapse_snapshot.take() else {
+            return false;
+        };
+        self.bodies = bodies;
+        self.prime_accelerations();
+        true
+    }
+
+    pub fn remove_body(&mut self, id: u32) {
         self.bodies.retain(|b| b.id != id);
     }
 
+    fn in_group(body: &CelestialBody, group: &str) -> bool {
+        body.group.as_deref() == Some(group)
+    }
+
+    // Group edits re-prime once at the end rather than per body. Each returns
+    // how many bodies it touched.
+    pub fn remove_group(&mut self, group: &str) -> usize {
+        let before = self.bodies.len();
+        self.bodies.retain(|b| !Self::in_group(b, group));
+        let removed = before - self.bodies.len();
+        if removed > 0 {
+            self.prime_accelerations();
+        }
+        removed
+    }
+
+    pub fn translate_group(&mut self, group: &str, offset: Vec3) -> usize {
+        let mut moved = 0;
+        for body in self.bodies.iter_mut().filter(|b| Self::in_group(b, group)) {
+            body.translate(offset);
+            moved += 1;
+        }
+        if moved > 0 {
+            self.prime_accelerations();
+        }
+        moved
+    }
+
+    pub fn recolor_group(&mut self, group: &str, color: &str) -> usize {
+        let mut recolored = 0;
+        for body in self.bodies.iter_mut().filter(|b| Self::in_group(b, group)) {
+            body.color = color.to_string();
+            recolored += 1;
+        }
+        recolored
+    }
+
     pub fn find_body_mut(&mut self, id: u32) -> Option<&mut CelestialBody> {
         self.bodies.iter_mut().find(|b| b.id == id)
     }
@@ -89,34 +1426,720 @@ impl SimulationState {
         self.bodies.iter().find(|b| b.id == id)
     }
 
+    // Returns the multiplier actually applied after clamping
+    pub fn set_speed_multiplier(&mut self, multiplier: f64) -> Result<f64, String> {
+        self.speed_multiplier = check_speed(multiplier)?.clamp(0.25, 8.0);
+        Ok(self.speed_multiplier)
+    }
+
+    pub fn set_gravity(&mut self, g: f64) -> Result<(), String> {
+        self.g = check_gravity(g)?;
+        self.prime_accelerations();
+        Ok(())
+    }
+
+    pub fn set_softening(&mut self, softening: f64) -> Result<(), String> {
+        self.softening = check_softening(softening)?;
+        self.prime_accelerations();
+        Ok(())
+    }
+
+    // Anything past 2 is clamped. Theta 0 opens every node, which is exact
+    // but no cheaper than brute force, so very low values use brute force.
+    pub fn set_theta(&mut self, theta: f64) -> Result<ThetaReport, String> {
+        self.theta = check_theta(theta)?.min(2.0);
+        let n = self.bodies.len();
+        let brute_force =
+            self.theta < MIN_TREE_THETA && self.base_path(n) == AccelerationPath::Tree;
+        let warning = if brute_force {
+            Some(format!(
+                "theta below {} runs {} bodies by brute force: {} terms per body, about {:.0}x \
+                 the work of theta {}",
+                MIN_TREE_THETA,
+                n,
+                n - 1,
+                (n - 1) as f64 / self.mean_tree_terms(default_theta()),
+                default_theta()
+            ))
+        } else if self.theta < LOW_THETA && self.pick_path(n) == AccelerationPath::Tree {
+            let ratio = self.mean_tree_terms(self.theta) / self.mean_tree_terms(default_theta());
+            Some(format!(
+                "theta {} opens most of the tree for {} bodies: about {:.1}x the work of theta {}",
+                self.theta,
+                n,
+                ratio,
+                default_theta()
+            ))
+        } else {
+            None
+        };
+        Ok(ThetaReport {
+            theta: self.theta,
+            requested: theta,
+            brute_force,
+            warning,
+        })
+    }
+
+    // Average terms a tree walk sums at `theta`, over a sample of bodies
+    fn mean_tree_terms(&self, theta: f64) -> f64 {
+        let positions: Vec<Vec3> = self.bodies.iter().map(|b| b.position).collect();
+        let masses: Vec<f64> = self.bodies.iter().map(|b| b.source_mass()).collect();
+        let (tree, outliers) = self.build_tree(&positions, &masses);
+        let params = barneshut::ForceParams {
+            g: self.g,
+            softening_sq: self.softening * self.softening,
+            theta,
+            kernel: self.softening_kernel,
+        };
+        let stride = positions.len().div_ceil(THETA_SAMPLE_BODIES).max(1);
+        let mut walk = Vec::new();
+        let mut samples = 0;
+        for i in (0..positions.len()).step_by(stride) {
+            tree.contributions(&positions[i], i, &params, &|_| params.softening_sq, &mut walk);
+            samples += 1;
+        }
+        (walk.len() as f64 / samples.max(1) as f64 + outliers.len() as f64).max(1.0)
+    }
+
+    // The same checks the setters make, for a state that arrived whole (an
+    // imported file)
+    pub fn check_settings(&self) -> Result<(), String> {
+        check_dt(self.dt)?;
+        check_gravity(self.g)?;
+        check_softening(self.softening)?;
+        check_theta(self.theta)?;
+        check_speed(self.speed_multiplier)?;
+        Ok(())
+    }
+
+    pub fn set_substeps(&mut self, substeps: u32) -> u32 {
+        self.substeps = substeps.clamp(1, MAX_SUBSTEPS);
+        self.substeps
+    }
+
+    pub fn set_softening_mode(&mut self, mode: SofteningMode) {
+        self.softening_mode = mode;
+        if mode == SofteningMode::Auto {
+            self.fit_softening();
+        }
+        self.prime_accelerations();
+    }
+
+    // Sets softening to a fraction of the mean spacing between bodies: the
+    // side of their bounding cube over the cube root of their count. Left
+    // alone with fewer than two bodies. Returns the new value.
+    pub fn fit_softening(&mut self) -> Option<f64> {
+        let positions: Vec<Vec3> = self.bodies.iter().map(|b| b.position).collect();
+        if positions.len() < 2 {
+            return None;
+        }
+        let first = positions[0];
+        let (lo, hi) = positions.iter().fold((first, first), |(lo, hi), p| {
+            (
+                Vec3::new(lo.x.min(p.x), lo.y.min(p.y), lo.z.min(p.z)),
+                Vec3::new(hi.x.max(p.x), hi.y.max(p.y), hi.z.max(p.z)),
+            )
+        });
+        let side = (hi.x - lo.x).max(hi.y - lo.y).max(hi.z - lo.z);
+        let softening = AUTO_SOFTENING_FRACTION * side / (positions.len() as f64).cbrt();
+        // Coincident bodies give no scale to go by
+        check_softening(softening).ok().filter(|&eps| eps > 0.0)?;
+        self.softening = softening;
+        Some(softening)
+    }
+
+    // Scenario loaders call this so each preset starts from settings suited
+    // to it instead of whatever the previous scenario or the user left behind.
+    pub fn apply_scenario_settings(&mut self, theta: f64, integrator: Integrator, softening: f64) {
+        self.theta = theta;
+        self.integrator = integrator;
+        self.softening = softening;
+    }
+
+    // `speed_multiplier` is simulated time per tick in units of the fixed dt.
+    // Whole steps are taken and the remainder carries over, so 0.5x steps on
+    // every other tick and 2x steps twice, each at the same dt.
     pub fn step(&mut self) -> Vec<CollisionEvent> {
         if self.paused || self.bodies.is_empty() {
             return Vec::new();
         }
+        self.step_accumulator += self.speed_multiplier;
+        let steps = self.step_accumulator.floor();
+        self.step_accumulator -= steps;
+        let collisions = self.on_pool(|sim| sim.advance(steps as u32));
+        if steps > 0.0 {
+            self.run_step_hook(&collisions);
+        }
+        collisions
+    }
 
-        let sub_steps = self.speed_multiplier.ceil() as u32;
-        let dt = self.dt * self.speed_multiplier / sub_steps as f64;
+    // Taken out for the call so the hook can borrow the state it lives in
+    fn run_step_hook(&mut self, collisions: &[CollisionEvent]) {
+        if let Some(mut hook) = self.step_hook.take() {
+            hook(self, collisions);
+            self.step_hook = Some(hook);
+        }
+    }
+
+    // One tick (exactly as `step`, so pausing and the speed multiplier
+    // apply), packaged with the resulting frame and the tick's events
+    pub fn next_frame(&mut self) -> FrameUpdate {
+        let collisions = self.step();
+        let mut frame = self.to_frame();
+        self.throttle_frame(&mut frame);
+        FrameUpdate {
+            frame,
+            collisions,
+            numerical_errors: self.take_numerical_errors(),
+            speed_clamps: self.take_speed_clamps(),
+        }
+    }
+
+    pub fn set_frame_lod(&mut self, lod: Option<FrameLod>) -> Result<(), String> {
+        if let Some(lod) = lod {
+            if !lod.distance.is_finite() || lod.distance <= 0.0 {
+                return Err(format!("LOD distance must be positive, got {}", lod.distance));
+            }
+            if lod.every == 0 {
+                return Err("LOD interval must be at least 1 frame".to_string());
+            }
+        }
+        self.frame_lod = lod;
+        self.lod = LodState::default();
+        Ok(())
+    }
+
+    // Drops the far bodies from all but every `every`th frame. Adding or
+    // removing a body or changing focus forces a full frame.
+    fn throttle_frame(&mut self, frame: &mut SimulationFrame) {
+        let Some(lod) = self.frame_lod else {
+            return;
+        };
+        let key = (self.bodies.len(), self.next_id, self.focus_body);
+        self.lod.frames += 1;
+        if self.lod.key != Some(key) || self.lod.frames >= lod.every {
+            self.lod = LodState {
+                frames: 0,
+                key: Some(key),
+            };
+            return;
+        }
+        let focus = self.focus_body.and_then(|id| self.find_body(id));
+        let center = focus.map_or_else(|| self.barycenter(), |f| f.position);
+        let held: BTreeSet<u32> = self
+            .bodies
+            .iter()
+            .filter(|b| Some(b.id) != self.focus_body)
+            .filter(|b| (b.position - center).magnitude() > lod.distance)
+            .map(|b| b.id)
+            .collect();
+        frame.bodies.retain(|b| !held.contains(&b.id));
+        frame.held = held.into_iter().collect();
+    }
+
+    // Endless `next_frame` calls; bound it with `take` or `take_while`
+    pub fn frames(&mut self) -> impl Iterator<Item = FrameUpdate> + '_ {
+        std::iter::from_fn(move || Some(self.next_frame()))
+    }
+
+    // Runs `f` on the physics pool, if one is set, so every rayon call under
+    // it uses only the pool's threads
+    fn on_pool<R: Send>(&mut self, f: impl FnOnce(&mut Self) -> R + Send) -> R {
+        match self.pool.clone() {
+            Some(pool) => pool.install(|| f(self)),
+            None => f(self),
+        }
+    }
+
+    // Clamped to 1..=cores. Returns the count applied.
+    pub fn set_physics_threads(&mut self, threads: usize) -> Result<usize, String> {
+        let cores = std::thread::available_parallelism().map_or(1, |n| n.get());
+        let threads = threads.clamp(1, cores);
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .thread_name(|i| format!("physics-{}", i))
+            .build()
+            .map_err(|e| e.to_string())?;
+        self.pool = Some(Arc::new(pool));
+        Ok(threads)
+    }
+
+    // Takes `steps` steps of dt, each split into `substeps` integrations
+    fn advance(&mut self, steps: u32) -> Vec<CollisionEvent> {
+        if steps == 0 {
+            return Vec::new();
+        }
+        self.telemetry.compute_ms = 0.0;
+        let substeps = self.substeps.max(1);
+        let dt = self.dt / substeps as f64;
 
         let mut all_collisions = Vec::new();
 
-        for _ in 0..sub_steps {
-            self.step_verlet(dt);
-            let collisions = self.check_collisions();
+        for _ in 0..steps * substeps {
+            // Purge up front so the start positions line up with the bodies
+            // that actually get integrated
+            self.purge_non_finite();
+            let start_positions: Vec<Vec3> = self.bodies.iter().map(|b| b.position).collect();
+            self.integrate(dt);
+            self.wrap_positions();
+            self.sim_time += dt;
+            for body in self.bodies.iter_mut() {
+                body.advance_orientation(dt);
+            }
+            let collisions = self.check_collisions(&start_positions, dt);
             all_collisions.extend(collisions);
+            if self.collision.mode == CollisionMode::Accretion {
+                all_collisions.extend(self.accrete_dust(dt));
+            }
+            self.clamp_speeds();
+            self.apply_settling_drag(dt);
+            self.record_apses(dt);
+        }
+        self.purge_non_finite();
+
+        if self.period_trails && self.tick.is_multiple_of(PERIOD_TRAIL_REFRESH_TICKS) {
+            self.fit_trails_to_periods();
         }
 
-        if self.tick % 2 == 0 {
+        if self.tick.is_multiple_of(TRAIL_STRIDE) {
+            self.sync_trail_origin();
+            let anchor = self.trail_origin.and_then(|id| self.find_body(id));
+            let (origin, frame_velocity) =
+                anchor.map_or((Vec3::zero(), Vec3::zero()), |b| (b.position, b.velocity));
             for body in self.bodies.iter_mut() {
-                if !body.is_fixed {
-                    body.record_trail();
+                // The origin body's own trail would be a single point
+                if !body.is_fixed && body.record_trail_enabled && Some(body.id) != self.trail_origin
+                {
+                    body.record_trail(self.sim_time, origin, frame_velocity);
+                    let speed = (body.velocity - frame_velocity).magnitude();
+                    self.speed_range = SpeedRange::include(self.speed_range, speed);
                 }
             }
         }
 
+        self.expire_bodies();
+        if self.spawn_exhaust {
+            self.spawn_exhaust_particles();
+        }
+
+        self.settle_ticks = self.settle_ticks.saturating_sub(1);
         self.tick += 1;
+        if self.snapshot_interval > 0 && self.tick.is_multiple_of(self.snapshot_interval) {
+            self.record_snapshot();
+        }
         all_collisions
     }
 
+    fn record_snapshot(&mut self) {
+        if self.snapshots.len() == MAX_SNAPSHOTS {
+            self.snapshots.pop_front();
+        }
+        let bodies = self
+            .bodies
+            .iter()
+            .map(|b| CelestialBody {
+                trail: VecDeque::new(),
+                ..b.clone()
+            })
+            .collect();
+        self.snapshots.push_back(Snapshot {
+            tick: self.tick,
+            sim_time: self.sim_time,
+            bodies,
+        });
+    }
+
+    // Turning snapshots off drops the history so it frees its memory
+    pub fn set_snapshot_interval(&mut self, interval: u64) {
+        self.snapshot_interval = interval;
+        if interval == 0 {
+            self.snapshots.clear();
+        }
+    }
+
+    // Oldest first; `tick` is what `predict_orbit_from` takes, and stays
+    // valid as older snapshots are dropped
+    pub fn snapshots(&self) -> Vec<SnapshotInfo> {
+        self.snapshots
+            .iter()
+            .map(|snap| SnapshotInfo {
+                tick: snap.tick,
+                sim_time: snap.sim_time,
+                body_count: snap.bodies.len(),
+            })
+            .collect()
+    }
+
+    // Counts down every lifetime and drops the bodies that ran out
+    fn expire_bodies(&mut self) {
+        self.bodies.retain_mut(|b| match b.lifetime.as_mut() {
+            Some(ticks) => {
+                *ticks = ticks.saturating_sub(1);
+                *ticks > 0
+            }
+            None => true,
+        });
+    }
+
+    // Puffs exhaust out of the nozzle of every spacecraft under thrust. The
+    // plume never evicts real bodies: it is skipped once the cap is reached.
+    fn spawn_exhaust_particles(&mut self) {
+        // (nozzle position, exhaust velocity, gravity felt at the nozzle)
+        let nozzles: Vec<(Vec3, Vec3, Vec3)> = self
+            .bodies
+            .iter()
+            .filter_map(|b| {
+                let thrust = b.thrust_acceleration();
+                let magnitude = thrust.magnitude();
+                if magnitude == 0.0 {
+                    return None;
+                }
+                let back = thrust.scale(-1.0 / magnitude);
+                let position = b.position + back.scale(1.5 * b.radius);
+                let velocity = b.velocity + back.scale(EXHAUST_SPEED);
+                Some((position, velocity, b.acceleration - thrust))
+            })
+            .collect();
+
+        for (position, velocity, gravity) in nozzles {
+            for _ in 0..EXHAUST_PER_TICK {
+                if self.bodies.len() >= self.max_bodies {
+                    return;
+                }
+                let spread = EXHAUST_SPEED * EXHAUST_SPREAD;
+                let jitter = Vec3::new(
+                    self.rng.random_range(-spread..spread),
+                    self.rng.random_range(-spread..spread),
+                    self.rng.random_range(-spread..spread),
+                );
+                let id = self.allocate_id();
+                let mut particle = CelestialBody::new(
+                    id,
+                    "Exhaust",
+                    position,
+                    velocity + jitter,
+                    1e-6,
+                    0.3,
+                    "#FFA040",
+                    false,
+                );
+                particle.is_test_particle = true;
+                particle.record_trail_enabled = false;
+                particle.lifetime = Some(self.exhaust_lifetime.max(1));
+                // Ready for the first kick without re-priming the whole scene
+                particle.acceleration = gravity;
+                self.bodies.push(particle);
+            }
+        }
+    }
+
+    // Hill sphere radius of `body_id` about `primary_id`:
+    // r_H = a (1 - e) cbrt(m / 3M), using the osculating orbit (periapsis).
+    // Unbound bodies fall back to their current distance from the primary.
+    pub fn hill_radius(&self, body_id: u32, primary_id: u32) -> Option<f64> {
+        let body = self.find_body(body_id)?;
+        let primary = self.find_body(primary_id)?;
+        if body_id == primary_id || primary.mass <= 0.0 || body.mass <= 0.0 {
+            return None;
+        }
+        let rel_pos = body.position - primary.position;
+        let rel_vel = body.velocity - primary.velocity;
+        let orbit = two_body_orbit(self.g * (primary.mass + body.mass), &rel_pos, &rel_vel);
+        let distance = match orbit.semi_major_axis {
+            Some(a) => a * (1.0 - orbit.eccentricity),
+            None => rel_pos.magnitude(),
+        };
+        Some(distance * (body.mass / (3.0 * primary.mass)).cbrt())
+    }
+
+    // Orbits about each free body's primary. Energies within this fraction of
+    // mu / r of zero count as parabolic.
+    pub fn body_orbits(&self) -> Vec<BodyOrbit> {
+        const PARABOLIC_TOLERANCE: f64 = 1e-3;
+        (0..self.bodies.len())
+            .filter(|&i| !self.bodies[i].is_fixed)
+            .filter_map(|i| {
+                let body = &self.bodies[i];
+                let primary = &self.bodies[self.primary_of(i)?];
+                let mu = self.g * (primary.mass + body.mass);
+                let rel_pos = body.position - primary.position;
+                let r = rel_pos.magnitude();
+                if mu <= 0.0 || r == 0.0 {
+                    return None;
+                }
+                let orbit = two_body_orbit(mu, &rel_pos, &(body.velocity - primary.velocity));
+                let kind = if orbit.specific_energy.abs() <= PARABOLIC_TOLERANCE * mu / r {
+                    OrbitKind::Parabolic
+                } else if orbit.specific_energy < 0.0 {
+                    OrbitKind::Bound
+                } else {
+                    OrbitKind::Hyperbolic
+                };
+                Some(BodyOrbit {
+                    body_id: body.id,
+                    primary_id: primary.id,
+                    specific_energy: orbit.specific_energy,
+                    kind,
+                    period: if kind == OrbitKind::Bound { orbit.period(mu) } else { None },
+                })
+            })
+            .collect()
+    }
+
+    // Fastest bound orbit in the scene, from each body's orbit about its primary
+    pub fn shortest_period(&self) -> Option<f64> {
+        self.body_orbits()
+            .iter()
+            .filter_map(|orbit| orbit.period)
+            .min_by(|a, b| a.total_cmp(b))
+    }
+
+    // Applies `dt`, checking it against the fastest orbit first. With
+    // `auto_reduce`, an under-resolving dt is cut to the largest safe value.
+    pub fn set_dt(&mut self, dt: f64, auto_reduce: bool) -> Result<DtReport, String> {
+        check_dt(dt)?;
+        let shortest_period = self.shortest_period();
+        // Substeps divide dt before it is integrated, so they buy back
+        // resolution: the check is on dt / substeps
+        let substeps = self.substeps.max(1) as f64;
+        let safe_dt = shortest_period.map(|period| period * substeps / MIN_STEPS_PER_PERIOD);
+        let mut applied = dt;
+        let mut warning = None;
+        if let Some(safe_dt) = safe_dt.filter(|&safe| dt > safe) {
+            warning = Some(format!(
+                "dt {} gives fewer than {} steps per orbit for the fastest body; {} or less is safe",
+                dt, MIN_STEPS_PER_PERIOD, safe_dt
+            ));
+            if auto_reduce {
+                applied = safe_dt;
+            }
+        }
+        self.dt = applied;
+        Ok(DtReport {
+            dt: applied,
+            requested: dt,
+            shortest_period,
+            warning,
+        })
+    }
+
+    // Sets each free body's trail length to cover one orbital period about
+    // the body pulling hardest on it, at the current dt and speed. Unbound
+    // bodies get the default length.
+    pub fn fit_trails_to_periods(&mut self) {
+        // Trails advance on ticks that step, which below 1x take one dt each
+        let ticks_per_time = 1.0 / (self.dt * self.speed_multiplier.max(1.0));
+        let lengths: Vec<usize> = (0..self.bodies.len())
+            .map(|i| {
+                let body = &self.bodies[i];
+                let Some(primary) = self.primary_of(i) else {
+                    return MAX_TRAIL_POINTS;
+                };
+                let primary = &self.bodies[primary];
+                let mu = self.g * (primary.mass + body.mass);
+                let orbit = two_body_orbit(
+                    mu,
+                    &(body.position - primary.position),
+                    &(body.velocity - primary.velocity),
+                );
+                match orbit.period(mu) {
+                    Some(period) => {
+                        let points = period * ticks_per_time / TRAIL_STRIDE as f64;
+                        (points.round() as usize).clamp(MIN_PERIOD_TRAIL, MAX_PERIOD_TRAIL)
+                    }
+                    None => MAX_TRAIL_POINTS,
+                }
+            })
+            .collect();
+        for (body, len) in self.bodies.iter_mut().zip(lengths) {
+            body.trail_max = len;
+        }
+    }
+
+    // Points stored relative to one body are meaningless against another, so
+    // a change of trail origin starts every trail afresh
+    fn sync_trail_origin(&mut self) {
+        let origin = match self.trail_frame {
+            TrailFrame::World => None,
+            TrailFrame::Focus => self.focus_body.filter(|id| self.find_body(*id).is_some()),
+        };
+        if origin != self.trail_origin {
+            for body in self.bodies.iter_mut() {
+                body.trail.clear();
+            }
+            self.speed_range = None;
+            self.trail_origin = origin;
+        }
+    }
+
+    pub fn set_trail_frame(&mut self, frame: TrailFrame) {
+        self.trail_frame = frame;
+        self.sync_trail_origin();
+    }
+
+    // Changing the focus body clears trails recorded in the focus frame
+    pub fn set_focus(&mut self, id: Option<u32>, relative_velocity: bool) {
+        self.focus_body = id;
+        self.focus_velocity = relative_velocity;
+        self.sync_trail_origin();
+    }
+
+    pub fn reset_trail_lengths(&mut self) {
+        for body in self.bodies.iter_mut() {
+            body.trail_max = MAX_TRAIL_POINTS;
+        }
+    }
+
+    // Returns false if there is no such body
+    pub fn set_precession_tracking(&mut self, body_id: u32, enabled: bool) -> bool {
+        if !enabled {
+            self.apsis_tracks.remove(&body_id);
+            return true;
+        }
+        if self.find_body(body_id).is_none() {
+            return false;
+        }
+        self.apsis_tracks.entry(body_id).or_default();
+        true
+    }
+
+    // Notes each tracked body's turn-around in r . v about its primary,
+    // interpolated within the step that crossed it
+    fn record_apses(&mut self, dt: f64) {
+        if self.apsis_tracks.is_empty() {
+            return;
+        }
+        let ids: Vec<u32> = self.apsis_tracks.keys().copied().collect();
+        for id in ids {
+            let Some(i) = self.bodies.iter().position(|b| b.id == id) else {
+                self.apsis_tracks.remove(&id);
+                continue;
+            };
+            let Some(p) = self.primary_of(i) else {
+                continue;
+            };
+            let (body, primary) = (&self.bodies[i], &self.bodies[p]);
+            let rel = self.separation(primary.position, body.position);
+            let radial = rel.dot(&(body.velocity - primary.velocity));
+            let primary_id = primary.id;
+            let sim_time = self.sim_time;
+
+            let track = self.apsis_tracks.get_mut(&id).unwrap();
+            if track.primary_id != Some(primary_id) {
+                *track = ApsisTrack {
+                    primary_id: Some(primary_id),
+                    ..ApsisTrack::default()
+                };
+            }
+            if let Some((last_rel, last_radial)) = track.last.replace((rel, radial)) {
+                let kind = if last_radial < 0.0 && radial >= 0.0 {
+                    ApsisKind::Periapsis
+                } else if last_radial > 0.0 && radial <= 0.0 {
+                    ApsisKind::Apoapsis
+                } else {
+                    continue;
+                };
+                let f = last_radial / (last_radial - radial);
+                let at = last_rel + (rel - last_rel).scale(f);
+                if track.apses.len() == MAX_APSES {
+                    track.apses.remove(0);
+                }
+                track.apses.push(Apsis {
+                    kind,
+                    direction: at.scale(1.0 / at.magnitude()),
+                    sim_time: sim_time - dt * (1.0 - f),
+                });
+            }
+        }
+    }
+
+    // Mean turn of the apsis line per orbit, from consecutive periapsides and
+    // consecutive apoapsides. None until the body has made a full orbit
+    // since tracking started (or its primary last changed).
+    pub fn precession_rate(&self, body_id: u32) -> Option<PrecessionReport> {
+        let track = self.apsis_tracks.get(&body_id)?;
+        let body = self.find_body(body_id)?;
+        let primary = self.find_body(track.primary_id?)?;
+        let axis = self
+            .separation(primary.position, body.position)
+            .cross(&(body.velocity - primary.velocity));
+        let axis = axis.scale(1.0 / axis.magnitude());
+        if !axis.x.is_finite() {
+            return None;
+        }
+
+        let (mut turn, mut elapsed, mut orbits) = (0.0, 0.0, 0);
+        for kind in [ApsisKind::Periapsis, ApsisKind::Apoapsis] {
+            let apses: Vec<&Apsis> = track.apses.iter().filter(|a| a.kind == kind).collect();
+            for pair in apses.windows(2) {
+                let (a, b) = (pair[0].direction, pair[1].direction);
+                turn += a.cross(&b).dot(&axis).atan2(a.dot(&b));
+                elapsed += pair[1].sim_time - pair[0].sim_time;
+                orbits += 1;
+            }
+        }
+        if orbits == 0 {
+            return None;
+        }
+        Some(PrecessionReport {
+            body_id,
+            primary_id: primary.id,
+            orbits,
+            degrees_per_orbit: (turn / orbits as f64).to_degrees(),
+            period: elapsed / orbits as f64,
+        })
+    }
+
+    // Index of the other body with the strongest pull on body `i`
+    fn primary_of(&self, i: usize) -> Option<usize> {
+        let pos = self.bodies[i].position;
+        self.bodies
+            .iter()
+            .enumerate()
+            .filter(|(j, b)| *j != i && !b.is_test_particle && b.mass > 0.0)
+            .map(|(j, b)| {
+                let diff = b.position - pos;
+                (j, b.mass / diff.dot(&diff))
+            })
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(j, _)| j)
+    }
+
+    // Patched-conic sphere of influence: the body whose (unsoftened) pull is
+    // strongest at `point`. Test particles exert no pull and never dominate.
+    pub fn dominant_body_at(&self, point: &Vec3) -> Option<u32> {
+        self.bodies
+            .iter()
+            .filter(|b| !b.is_test_particle && b.mass > 0.0)
+            .map(|b| {
+                let diff = b.position - *point;
+                (b.id, self.g * b.mass / diff.dot(&diff))
+            })
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(id, _)| id)
+    }
+
+    // Exactly one fixed-dt step, paused or not and whatever the speed
+    pub fn step_once(&mut self) -> Vec<CollisionEvent> {
+        if self.bodies.is_empty() {
+            return Vec::new();
+        }
+        let collisions = self.on_pool(|sim| sim.advance(1));
+        self.run_step_hook(&collisions);
+        collisions
+    }
+
+    // Callers purge non-finite bodies first; with finite positions,
+    // velocities and accelerations going in, the drift stays finite, so a bad
+    // body never reaches the tree's center-of-mass sums
+    fn integrate(&mut self, dt: f64) {
+        match self.integrator {
+            Integrator::Verlet => self.step_verlet(dt),
+            Integrator::Rk4 => self.step_rk4(dt),
+        }
+    }
+
     fn step_verlet(&mut self, dt: f64) {
         for body in self.bodies.iter_mut() {
             if body.is_fixed {
@@ -132,224 +2155,1255 @@ impl SimulationState {
 
         self.compute_accelerations();
 
-        // Apply spacecraft thrust
-        for body in self.bodies.iter_mut() {
-            if body.body_type == BodyType::Spacecraft && body.fuel > 0.0 {
-                let thrust_mag = body.thrust.magnitude();
-                if thrust_mag > 0.001 {
-                    let thrust_accel = body.thrust.scale(1.0 / body.mass);
-                    body.acceleration += thrust_accel;
-                    body.fuel = (body.fuel - thrust_mag * dt * 0.1).max(0.0);
-                }
-            }
+        let thrust = self.burn_thrust(dt);
+        for (body, t) in self.bodies.iter_mut().zip(thrust.iter()) {
+            body.acceleration += *t;
+        }
+
+        for (i, body) in self.bodies.iter_mut().enumerate() {
+            if body.is_fixed {
+                continue;
+            }
+            body.velocity += (old_accelerations[i] + body.acceleration).scale(0.5 * dt);
+        }
+    }
+
+    // Classic fourth-order Runge-Kutta. Costs four force evaluations per step
+    // (plus one to leave `acceleration` current) but holds tight periodic
+    // orbits like the figure-eight together far longer than Verlet at equal dt.
+    fn step_rk4(&mut self, dt: f64) {
+        let x0: Vec<Vec3> = self.bodies.iter().map(|b| b.position).collect();
+        let v0: Vec<Vec3> = self.bodies.iter().map(|b| b.velocity).collect();
+
+        // Thrust is held constant across the stages and fuel is burned once
+        let thrust = self.burn_thrust(dt);
+
+        let a1 = self.stage_accelerations(&x0, &v0, &thrust);
+        let x2 = offset_all(&x0, &v0, 0.5 * dt);
+        let v2 = offset_all(&v0, &a1, 0.5 * dt);
+        let a2 = self.stage_accelerations(&x2, &v2, &thrust);
+        let x3 = offset_all(&x0, &v2, 0.5 * dt);
+        let v3 = offset_all(&v0, &a2, 0.5 * dt);
+        let a3 = self.stage_accelerations(&x3, &v3, &thrust);
+        let x4 = offset_all(&x0, &v3, dt);
+        let v4 = offset_all(&v0, &a3, dt);
+        let a4 = self.stage_accelerations(&x4, &v4, &thrust);
+
+        for (i, body) in self.bodies.iter_mut().enumerate() {
+            if body.is_fixed {
+                continue;
+            }
+            body.position = x0[i]
+                + (v0[i] + v2[i].scale(2.0) + v3[i].scale(2.0) + v4[i]).scale(dt / 6.0);
+            body.velocity = v0[i]
+                + (a1[i] + a2[i].scale(2.0) + a3[i].scale(2.0) + a4[i]).scale(dt / 6.0);
+        }
+
+        self.compute_accelerations();
+        for (body, t) in self.bodies.iter_mut().zip(thrust.iter()) {
+            body.acceleration += *t;
+        }
+    }
+
+    // Moves the free bodies to the given stage positions and velocities and
+    // returns the total acceleration (gravity + thrust) each body feels there.
+    // The velocities matter to the post-Newtonian term and contact damping.
+    fn stage_accelerations(
+        &mut self,
+        positions: &[Vec3],
+        velocities: &[Vec3],
+        thrust: &[Vec3],
+    ) -> Vec<Vec3> {
+        for ((body, p), v) in self.bodies.iter_mut().zip(positions).zip(velocities) {
+            if !body.is_fixed {
+                body.position = *p;
+                body.velocity = *v;
+            }
+        }
+        self.compute_accelerations();
+        self.bodies
+            .iter()
+            .zip(thrust.iter())
+            .map(|(b, t)| b.acceleration + *t)
+            .collect()
+    }
+
+    // Returns the thrust acceleration of each body for this step and burns the
+    // corresponding fuel. Non-spacecraft and empty tanks contribute zero.
+    fn burn_thrust(&mut self, dt: f64) -> Vec<Vec3> {
+        self.bodies
+            .iter_mut()
+            .map(|body| {
+                let accel = body.thrust_acceleration();
+                if accel.magnitude() > 0.0 {
+                    let burned = body.thrust.magnitude() * dt * FUEL_PER_IMPULSE;
+                    body.fuel = (body.fuel - burned).max(0.0);
+                }
+                accel
+            })
+            .collect()
+    }
+
+    // Refreshes `acceleration` outside of a step (after edits, loads, imports)
+    // so it carries the same gravity + thrust total the integrator leaves
+    // behind. No fuel is burned.
+    fn refresh_accelerations(&mut self) {
+        self.compute_accelerations();
+        for body in self.bodies.iter_mut() {
+            body.acceleration += body.thrust_acceleration();
+        }
+    }
+
+    // Crossover points differ between machines; tune them against telemetry
+    pub fn set_accel_thresholds(&mut self, brute_max: usize, gpu_min: usize) -> Result<(), String> {
+        if brute_max >= gpu_min {
+            return Err(format!(
+                "brute_max ({}) must be below gpu_min ({})",
+                brute_max, gpu_min
+            ));
+        }
+        self.brute_max = brute_max;
+        self.gpu_min = gpu_min;
+        Ok(())
+    }
+
+    fn compute_accelerations(&mut self) {
+        let start = Instant::now();
+        let path = self.run_solver();
+        self.apply_nongrav();
+        if self.collision.mode == CollisionMode::Soft {
+            self.apply_contact_springs();
+        }
+        self.telemetry.path = path;
+        self.telemetry.body_count = self.bodies.len();
+        self.telemetry.gpu_active = self.use_gpu && self.gpu.is_some();
+        self.telemetry.compute_ms += start.elapsed().as_secs_f64() * 1000.0;
+    }
+
+    // Position-dependent, so it rides along with gravity and every integrator
+    // stage sees it, unlike thrust which is held fixed across a step
+    fn apply_nongrav(&mut self) {
+        if self.bodies.iter().all(|b| b.nongrav.is_none()) {
+            return;
+        }
+        let stars: Vec<Vec3> = self
+            .bodies
+            .iter()
+            .filter(|b| b.body_type == BodyType::Star)
+            .map(|b| b.position)
+            .collect();
+        for body in self.bodies.iter_mut() {
+            if body.nongrav.is_none() || body.is_fixed {
+                continue;
+            }
+            let nearest = stars.iter().min_by(|a, b| {
+                let da = (**a - body.position).magnitude();
+                let db = (**b - body.position).magnitude();
+                da.total_cmp(&db)
+            });
+            if let Some(star) = nearest {
+                body.acceleration += body.nongrav_acceleration(*star);
+            }
+        }
+    }
+
+    // Vector from `from` to `to`, via the nearest periodic image when the box
+    // is on
+    fn separation(&self, from: Vec3, to: Vec3) -> Vec3 {
+        let diff = to - from;
+        match self.periodic {
+            Some(size) => {
+                let wrap = |d: f64| d - size * (d / size).round();
+                Vec3::new(wrap(diff.x), wrap(diff.y), wrap(diff.z))
+            }
+            None => diff,
+        }
+    }
+
+    // Folds bodies that left the periodic box back in through the opposite
+    // face. Their trails are dropped rather than drawn across the box.
+    fn wrap_positions(&mut self) {
+        let Some(size) = self.periodic else {
+            return;
+        };
+        let half = 0.5 * size;
+        let wrap = |v: f64| v - size * ((v + half) / size).floor();
+        for body in self.bodies.iter_mut() {
+            let p = body.position;
+            let wrapped = Vec3::new(wrap(p.x), wrap(p.y), wrap(p.z));
+            if wrapped.x != p.x || wrapped.y != p.y || wrapped.z != p.z {
+                body.position = wrapped;
+                body.trail.clear();
+            }
+        }
+    }
+
+    // Sets or clears the periodic box, folding every body into it at once
+    pub fn set_force_exponent(&mut self, exponent: f64) -> Result<(), String> {
+        if !FORCE_EXPONENT_RANGE.contains(&exponent) {
+            return Err(format!(
+                "force exponent must lie in [{}, {}], got {}",
+                FORCE_EXPONENT_RANGE.start(),
+                FORCE_EXPONENT_RANGE.end(),
+                exponent
+            ));
+        }
+        self.force_exponent = exponent;
+        self.prime_accelerations();
+        Ok(())
+    }
+
+    // `light_speed` None keeps the current value
+    pub fn set_relativistic(
+        &mut self,
+        enabled: bool,
+        light_speed: Option<f64>,
+    ) -> Result<(), String> {
+        if let Some(c) = light_speed {
+            if !c.is_finite() || c <= 0.0 {
+                return Err(format!("light speed must be positive, got {}", c));
+            }
+            self.light_speed = c;
+        }
+        self.relativistic = enabled;
+        self.prime_accelerations();
+        Ok(())
+    }
+
+    // The cap applies to future additions; bodies already over it are kept
+    pub fn set_max_bodies(
+        &mut self,
+        max_bodies: usize,
+        policy: Option<OverflowPolicy>,
+    ) -> Result<usize, String> {
+        if max_bodies == 0 {
+            return Err("body limit must be at least 1".to_string());
+        }
+        self.max_bodies = max_bodies;
+        if let Some(policy) = policy {
+            self.overflow_policy = policy;
+        }
+        Ok(self.max_bodies)
+    }
+
+    // None removes the cap
+    pub fn set_max_speed(&mut self, max_speed: Option<f64>) -> Result<(), String> {
+        if let Some(speed) = max_speed {
+            if !speed.is_finite() || speed <= 0.0 {
+                return Err(format!("speed cap must be positive, got {}", speed));
+            }
+        }
+        self.max_speed = max_speed;
+        Ok(())
+    }
+
+    pub fn set_periodic(&mut self, size: Option<f64>) -> Result<(), String> {
+        if let Some(size) = size {
+            if !size.is_finite() || size <= 0.0 {
+                return Err(format!("Box size must be positive, got {}", size));
+            }
+        }
+        self.periodic = size;
+        self.wrap_positions();
+        self.prime_accelerations();
+        Ok(())
+    }
+
+    // Penalty contact for soft mode: every overlapping pair is pushed apart
+    // along the line of centers by stiffness * depth, less damping on the
+    // approach speed (never pulling the pair together). Fixed bodies take no
+    // acceleration but still push. Debris is left out, as the merge and bounce
+    // passes leave it out, and so are test particles, which have no mass for
+    // the force to act on.
+    fn apply_contact_springs(&mut self) {
+        let n = self.bodies.len();
+        let stiffness = self.collision.stiffness.max(0.0);
+        let damping = self.collision.contact_damping.max(0.0);
+        let mut accels = vec![Vec3::zero(); n];
+        let excluded: Vec<bool> =
+            self.bodies.iter().map(|b| b.lifetime.is_some() || b.is_test_particle).collect();
+
+        for i in (0..n).filter(|&i| !excluded[i]) {
+            for j in ((i + 1)..n).filter(|&j| !excluded[j]) {
+                let (a, b) = (&self.bodies[i], &self.bodies[j]);
+                let diff = self.separation(a.position, b.position);
+                let dist = diff.magnitude();
+                let depth = a.radius + b.radius - dist;
+                if depth <= 0.0 || dist == 0.0 {
+                    continue;
+                }
+                let normal = diff.scale(1.0 / dist);
+                let approach = (b.velocity - a.velocity).dot(&normal);
+                let force = (stiffness * depth - damping * approach).max(0.0);
+                accels[i] = accels[i] - normal.scale(force / a.mass.abs());
+                accels[j] += normal.scale(force / b.mass.abs());
+            }
+        }
+
+        for (body, accel) in self.bodies.iter_mut().zip(accels) {
+            if !body.is_fixed {
+                body.acceleration += accel;
+            }
+        }
+    }
+
+    fn run_solver(&mut self) -> AccelerationPath {
+        let positions: Vec<Vec3> = self.bodies.iter().map(|b| b.position).collect();
+        let masses: Vec<f64> = self.bodies.iter().map(|b| b.source_mass()).collect();
+        let attrs: Vec<BodyAttributes> = self.bodies.iter().map(BodyAttributes::of).collect();
+        let (accels, path, tree_stats) = self.solve(&positions, &masses, Some(&attrs));
+
+        (self.telemetry.tree_nodes, self.telemetry.tree_depth) = tree_stats;
+        for (body, accel) in self.bodies.iter_mut().zip(accels) {
+            body.acceleration = if body.is_fixed { Vec3::zero() } else { accel };
+        }
+        path
+    }
+
+    // Gravity felt at each position from the given source masses, through the
+    // same path a step would pick. `attrs`, one per position, feed
+    // radius-scaled softening, smooth accretion and the post-Newtonian term;
+    // without them those fall back to constant softening and plain Newtonian
+    // gravity. Fixed bodies are not special-cased here.
+    pub fn accelerations_for(
+        &self,
+        positions: &[Vec3],
+        masses: &[f64],
+        attrs: Option<&[BodyAttributes]>,
+    ) -> Vec<Vec3> {
+        self.solve(positions, masses, attrs).0
+    }
+
+    // Also returns the path taken and the tree's (node count, depth), zero
+    // unless the tree ran
+    fn solve(
+        &self,
+        positions: &[Vec3],
+        masses: &[f64],
+        attrs: Option<&[BodyAttributes]>,
+    ) -> (Vec<Vec3>, AccelerationPath, (usize, usize)) {
+        debug_assert!(attrs.is_none_or(|a| a.len() == positions.len()));
+        let path = self.pick_path(positions.len());
+        match (path, &self.gpu) {
+            // The mesh has its own smoothing, so softening kernels and
+            // radius-scaled softening don't apply on this path
+            (AccelerationPath::Mesh, _) => {
+                let accels = pm::compute_accelerations(
+                    positions,
+                    masses,
+                    self.g,
+                    self.softening,
+                    self.pm_grid,
+                );
+                (accels, path, (0, 0))
+            }
+            (AccelerationPath::Gpu, Some(gpu)) => {
+                let accels = gpu.compute_accelerations(
+                    positions,
+                    masses,
+                    self.g,
+                    self.softening * self.softening,
+                    self.softening_kernel,
+                    self.gpu_precise,
+                );
+                (accels, path, (0, 0))
+            }
+            (AccelerationPath::Tree, _) => {
+                let (accels, stats) = self.tree_accelerations(positions, masses, attrs);
+                (accels, path, stats)
+            }
+            _ => (
+                self.brute_accelerations(positions, masses, attrs),
+                AccelerationPath::Brute,
+                (0, 0),
+            ),
+        }
+    }
+
+    // The solver a step over `n` bodies would use
+    pub fn active_solver(&self) -> ActiveSolver {
+        ActiveSolver {
+            path: self.pick_path(self.bodies.len()),
+            body_count: self.bodies.len(),
+            gpu_initialized: self.gpu.is_some(),
+        }
+    }
+
+    fn pick_path(&self, n: usize) -> AccelerationPath {
+        match self.base_path(n) {
+            AccelerationPath::Tree if self.theta < MIN_TREE_THETA => AccelerationPath::Brute,
+            path => path,
+        }
+    }
+
+    // The path by body count and force law alone, before theta is considered
+    fn base_path(&self, n: usize) -> AccelerationPath {
+        // The tree, GPU and mesh all assume a plain pairwise 1/r^2 pull
+        // between bodies in open space
+        if !self.is_inverse_square() || self.relativistic || self.periodic.is_some() {
+            return AccelerationPath::Brute;
+        }
+        if self.solver == SolverKind::ParticleMesh {
+            return AccelerationPath::Mesh;
+        }
+        let gpu_fits = self.gpu.as_ref().is_some_and(|gpu| n <= gpu.max_bodies());
+        if n > self.gpu_min && self.use_gpu && gpu_fits {
+            return AccelerationPath::Gpu;
+        }
+        if n > self.brute_max {
+            AccelerationPath::Tree
+        } else {
+            AccelerationPath::Brute
+        }
+    }
+
+    // Splits the gravity on `body_id` into per-source terms, largest first,
+    // keeping the top `top_k`
+    pub fn explain_acceleration(
+        &self,
+        body_id: u32,
+        top_k: usize,
+    ) -> Option<AccelerationBreakdown> {
+        let i = self.bodies.iter().position(|b| b.id == body_id)?;
+        let positions: Vec<Vec3> = self.bodies.iter().map(|b| b.position).collect();
+        let masses: Vec<f64> = self.bodies.iter().map(|b| b.source_mass()).collect();
+        let attrs: Vec<BodyAttributes> = self.bodies.iter().map(BodyAttributes::of).collect();
+        let path = self.pick_path(positions.len());
+
+        let mut terms: Vec<(ForceSource, Vec3)> = Vec::new();
+        if path == AccelerationPath::Tree {
+            let (tree, outliers) = self.build_tree(&positions, &masses);
+            let params = barneshut::ForceParams {
+                g: self.g,
+                softening_sq: self.softening * self.softening,
+                theta: self.theta,
+                kernel: self.softening_kernel,
+            };
+            let leaf_softening_sq =
+                |j: usize| self.pair_softening_sq(&positions, i, j, Some(&attrs));
+            let mut walk = Vec::new();
+            tree.contributions(&positions[i], i, &params, &leaf_softening_sq, &mut walk);
+            terms.extend(walk.into_iter().map(|term| {
+                let source = match term.body_index {
+                    Some(j) => ForceSource::Body {
+                        id: self.bodies[j].id,
+                        name: self.bodies[j].name.clone(),
+                    },
+                    None => ForceSource::Node {
+                        center_of_mass: term.center_of_mass,
+                        mass: term.mass,
+                        size: term.size,
+                    },
+                };
+                (source, term.accel)
+            }));
+            for j in outliers.into_iter().filter(|&j| j != i) {
+                let source = ForceSource::Body {
+                    id: self.bodies[j].id,
+                    name: self.bodies[j].name.clone(),
+                };
+                terms.push((source, self.outlier_accel(&positions, &masses, i, j, Some(&attrs))));
+            }
+        } else {
+            for j in (0..positions.len()).filter(|&j| j != i && masses[j] != 0.0) {
+                let diff = self.separation(positions[i], positions[j]);
+                let dist_sq = diff.dot(&diff);
+                let softening_sq = self.pair_softening_sq(&positions, i, j, Some(&attrs));
+                let inv_r3 = self.force_factor(dist_sq, softening_sq);
+                let correction = self.post_newtonian_factor(&attrs[i], &attrs[j], diff, dist_sq);
+                let source = ForceSource::Body {
+                    id: self.bodies[j].id,
+                    name: self.bodies[j].name.clone(),
+                };
+                terms.push((source, diff.scale(self.g * masses[j] * inv_r3 * correction)));
+            }
+        }
+
+        let gravity = terms.iter().fold(Vec3::zero(), |sum, (_, accel)| sum + *accel);
+        let term_count = terms.len();
+        terms.sort_by(|a, b| b.1.magnitude().total_cmp(&a.1.magnitude()));
+        terms.truncate(top_k);
+        Some(AccelerationBreakdown {
+            body_id,
+            path,
+            total: self.bodies[i].acceleration,
+            gravity,
+            term_count,
+            contributors: terms
+                .into_iter()
+                .map(|(source, acceleration)| AccelContribution {
+                    source,
+                    magnitude: acceleration.magnitude(),
+                    acceleration,
+                })
+                .collect(),
+        })
+    }
+
+    fn brute_accelerations(
+        &self,
+        positions: &[Vec3],
+        masses: &[f64],
+        attrs: Option<&[BodyAttributes]>,
+    ) -> Vec<Vec3> {
+        let n = positions.len();
+        let mut accels = vec![Vec3::zero(); n];
+
+        for (i, accel) in accels.iter_mut().enumerate() {
+            for j in 0..n {
+                if i == j || masses[j] == 0.0 {
+                    continue;
+                }
+                let diff = self.separation(positions[i], positions[j]);
+                let dist_sq = diff.x * diff.x + diff.y * diff.y + diff.z * diff.z;
+                let softening_sq = self.pair_softening_sq(positions, i, j, attrs);
+                let inv_r3 = self.force_factor(dist_sq, softening_sq);
+                let correction = match attrs {
+                    Some(attrs) => self.post_newtonian_factor(&attrs[i], &attrs[j], diff, dist_sq),
+                    None => 1.0,
+                };
+                *accel += diff.scale(self.g * masses[j] * inv_r3 * correction);
+            }
+        }
+        accels
+    }
+
+    // 1 + 3 h^2 / (r^2 c^2), with h the specific angular momentum of i about
+    // j: the leading correction that makes near-Keplerian orbits precess.
+    // `diff` points from i to j.
+    fn post_newtonian_factor(
+        &self,
+        i: &BodyAttributes,
+        j: &BodyAttributes,
+        diff: Vec3,
+        dist_sq: f64,
+    ) -> f64 {
+        if !self.relativistic || dist_sq == 0.0 || self.light_speed <= 0.0 {
+            return 1.0;
+        }
+        let rel_vel = i.velocity - j.velocity;
+        let h = diff.cross(&rel_vel);
+        let c_sq = self.light_speed * self.light_speed;
+        1.0 + 3.0 * h.dot(&h) / (dist_sq * c_sq)
+    }
+
+    fn is_inverse_square(&self) -> bool {
+        self.force_exponent == 2.0
+    }
+
+    // |F| / (G m r) for the configured force law. Other exponents use
+    // Plummer-style softening: the kernels are only defined for 1/r^2.
+    fn force_factor(&self, dist_sq: f64, softening_sq: f64) -> f64 {
+        if self.is_inverse_square() {
+            return self.softening_kernel.inv_r3(dist_sq, softening_sq);
+        }
+        (dist_sq + softening_sq).powf(-0.5 * (self.force_exponent + 1.0))
+    }
+
+    // Radius-scaled softening and smooth accretion need radii, so positions
+    // without attributes get the constant value
+    fn pair_softening_sq(
+        &self,
+        positions: &[Vec3],
+        i: usize,
+        j: usize,
+        attrs: Option<&[BodyAttributes]>,
+    ) -> f64 {
+        let contact = attrs.map(|a| a[i].radius + a[j].radius);
+        let base = match (self.softening_mode, contact) {
+            (SofteningMode::RadiusScaled, Some(contact)) => {
+                let eps = RADIUS_SOFTENING_FACTOR * contact;
+                eps * eps
+            }
+            _ => self.softening * self.softening,
+        };
+        let Some(contact) = contact.filter(|_| self.smooth_accretion) else {
+            return base;
+        };
+        // Grows smoothly from nothing at the ramp start to the full radius
+        // sum at contact. Softened that much, the pull of an overlapping pair
+        // never exceeds G m / (r1 + r2)^2 and falls to zero as they coincide.
+        let diff = self.separation(positions[i], positions[j]);
+        let t = ((ACCRETION_RAMP_START * contact - diff.magnitude())
+            / ((ACCRETION_RAMP_START - 1.0) * contact))
+            .clamp(0.0, 1.0);
+        let eps = contact * t * t * (3.0 - 2.0 * t);
+        base.max(eps * eps)
+    }
+
+    // The tree plus the massive bodies left outside frozen bounds, which the
+    // caller has to add one by one
+    fn build_tree(&self, positions: &[Vec3], masses: &[f64]) -> (barneshut::OctreeNode, Vec<usize>) {
+        let Some(bounds) = self.tree_bounds else {
+            return (barneshut::build_octree(positions, masses), Vec::new());
+        };
+        let outliers = (0..positions.len())
+            .filter(|&j| masses[j] != 0.0 && !bounds.contains(&positions[j]))
+            .collect();
+        (barneshut::build_octree_in(bounds, positions, masses), outliers)
+    }
+
+    // Direct pull of j on i. The tree never runs with periodic boundaries or
+    // other force laws, so this is plain softened 1/r^2.
+    fn outlier_accel(
+        &self,
+        positions: &[Vec3],
+        masses: &[f64],
+        i: usize,
+        j: usize,
+        attrs: Option<&[BodyAttributes]>,
+    ) -> Vec3 {
+        let diff = positions[j] - positions[i];
+        let softening_sq = self.pair_softening_sq(positions, i, j, attrs);
+        diff.scale(self.g * masses[j] * self.force_factor(diff.dot(&diff), softening_sq))
+    }
+
+    // Freezes the tree around the bodies that are still bound to the system,
+    // leaving out anything on its way out. Returns the new bounds.
+    pub fn recenter_tree(&mut self) -> Option<TreeBounds> {
+        let total_mass: f64 = self.bodies.iter().map(|b| b.source_mass()).sum();
+        let center = self.barycenter();
+        let drift = if total_mass > 0.0 {
+            self.total_momentum().scale(1.0 / total_mass)
+        } else {
+            Vec3::zero()
+        };
+        // Positive energy against the whole system's mass at its barycenter
+        let escaping = |b: &CelestialBody| {
+            let v = b.velocity - drift;
+            let r = (b.position - center).magnitude();
+            r > 0.0 && 0.5 * v.dot(&v) > self.g * total_mass / r
+        };
+        let bound: Vec<Vec3> = self
+            .bodies
+            .iter()
+            .filter(|b| b.source_mass() != 0.0 && !escaping(b))
+            .map(|b| b.position)
+            .collect();
+        if bound.is_empty() {
+            return None;
+        }
+        let bounds = TreeBounds::fit(&bound);
+        self.tree_bounds = Some(bounds);
+        self.prime_accelerations();
+        Some(bounds)
+    }
+
+    fn tree_accelerations(
+        &self,
+        positions: &[Vec3],
+        masses: &[f64],
+        attrs: Option<&[BodyAttributes]>,
+    ) -> (Vec<Vec3>, (usize, usize)) {
+        let (tree, outliers) = self.build_tree(positions, masses);
+        let params = barneshut::ForceParams {
+            g: self.g,
+            softening_sq: self.softening * self.softening,
+            theta: self.theta,
+            kernel: self.softening_kernel,
+        };
+
+        // Bodies are spread across threads, but each body's own sum is a single
+        // sequential tree walk in a fixed child order, and `collect` keeps index
+        // order. The result is bit-identical whatever the thread count or
+        // scheduling, so recorded runs replay exactly.
+        let accels: Vec<Vec3> = (0..positions.len())
+            .into_par_iter()
+            .map(|i| {
+                let leaf_softening_sq = |j: usize| self.pair_softening_sq(positions, i, j, attrs);
+                let accel =
+                    tree.compute_acceleration(&positions[i], i, &params, &leaf_softening_sq);
+                outliers
+                    .iter()
+                    .filter(|&&j| j != i)
+                    .fold(accel, |sum, &j| {
+                        sum + self.outlier_accel(positions, masses, i, j, attrs)
+                    })
+            })
+            .collect();
+        (accels, tree.stats())
+    }
+
+    // Exchanges an impulse along `normal` (pointing from i to j) between two
+    // touching bodies, then pushes them apart so they no longer overlap.
+    // Fixed bodies act as infinitely heavy. Returns true for an impact and
+    // false for a resting contact or a pair already separating.
+    fn resolve_contact(&mut self, i: usize, j: usize, normal: Vec3, dt: f64) -> bool {
+        let inv_mass = |b: &CelestialBody| if b.is_fixed { 0.0 } else { 1.0 / b.mass.abs() };
+        let wi = inv_mass(&self.bodies[i]);
+        let wj = inv_mass(&self.bodies[j]);
+        let w = wi + wj;
+        let dist = normal.magnitude();
+        if w == 0.0 || dist == 0.0 {
+            return false;
         }
+        let n = normal.scale(1.0 / dist);
 
-        for (i, body) in self.bodies.iter_mut().enumerate() {
-            if body.is_fixed {
-                continue;
-            }
-            body.velocity += (old_accelerations[i] + body.acceleration).scale(0.5 * dt);
+        let overlap = self.bodies[i].radius + self.bodies[j].radius;
+        let end_dist = self
+            .separation(self.bodies[i].position, self.bodies[j].position)
+            .magnitude();
+        if end_dist < overlap {
+            let push = n.scale((overlap - end_dist) / w);
+            self.bodies[i].position = self.bodies[i].position - push.scale(wi);
+            self.bodies[j].position += push.scale(wj);
         }
-    }
-
-    fn compute_accelerations(&mut self) {
-        let n = self.bodies.len();
 
-        if n > 500 {
-            if let Some(gpu) = &self.gpu {
-                self.compute_accelerations_gpu(gpu.clone());
-                return;
-            }
+        let rel_vel = self.bodies[j].velocity - self.bodies[i].velocity;
+        let approach = rel_vel.dot(&n);
+        if approach >= 0.0 {
+            return false;
         }
 
-        if n > 50 {
-            self.compute_accelerations_barneshut();
+        // Speed the pair's mutual pull builds up over one step. A rebound the
+        // pull cancels within a few steps can't be resolved at this dt and
+        // only feeds a jitter loop, so it counts as resting too.
+        let rel_accel = self.bodies[j].acceleration - self.bodies[i].acceleration;
+        let settle_speed = (-rel_accel.dot(&n) * dt).max(0.0);
+        let restitution = self.collision.restitution.clamp(0.0, 1.0);
+        let rebound = -approach * restitution;
+        let resting = -approach < self.collision.resting_speed
+            || rebound < self.collision.resting_speed + RESOLVABLE_BOUNCE_STEPS * settle_speed;
+        let restitution = if resting { 0.0 } else { restitution };
+        let normal_impulse = -(1.0 + restitution) * approach / w;
+
+        // Friction opposes sliding, up to stopping it outright
+        let tangential = rel_vel - n.scale(approach);
+        let slide = tangential.magnitude();
+        let friction_impulse = if slide > 0.0 {
+            let stop = slide / w;
+            tangential.scale(-stop.min(self.collision.friction * normal_impulse) / slide)
         } else {
-            self.compute_accelerations_brute();
-        }
+            Vec3::zero()
+        };
+
+        let impulse = n.scale(normal_impulse) + friction_impulse;
+        self.bodies[i].velocity = self.bodies[i].velocity - impulse.scale(wi);
+        self.bodies[j].velocity += impulse.scale(wj);
+        !resting
     }
 
-    fn compute_accelerations_brute(&mut self) {
-        let n = self.bodies.len();
-        let mut accels = vec![Vec3::zero(); n];
+    // Moves a share of each dust particle's mass and momentum into the
+    // heaviest body whose capture radius it is inside, shifting the accretor
+    // so the pair's center of mass stays put. Dust that has dwindled to
+    // nothing is absorbed outright and reported like a merge.
+    fn accrete_dust(&mut self, dt: f64) -> Vec<CollisionEvent> {
+        let capture = self.collision.capture_radius.max(1.0);
+        let fraction = 1.0 - (-self.collision.accretion_rate.max(0.0) * dt).exp();
+        let bodies = &self.bodies;
+        let accretors: Vec<Option<usize>> = (0..bodies.len())
+            .into_par_iter()
+            .map(|d| {
+                (0..bodies.len())
+                    .filter(|&a| is_dust(&bodies[d], &bodies[a]))
+                    .filter(|&a| {
+                        let reach = capture * bodies[a].radius;
+                        let diff = self.separation(bodies[a].position, bodies[d].position);
+                        diff.dot(&diff) < reach * reach
+                    })
+                    .max_by(|&a, &b| bodies[a].mass.total_cmp(&bodies[b].mass))
+            })
+            .collect();
 
-        for i in 0..n {
-            if self.bodies[i].is_fixed {
+        let mut collisions = Vec::new();
+        let mut absorbed = vec![false; self.bodies.len()];
+        for (d, accretor) in accretors.into_iter().enumerate() {
+            // An accretor can itself be dust to something heavier and have
+            // been finished off earlier in this pass
+            let Some(a) = accretor.filter(|&a| !absorbed[a]) else {
                 continue;
+            };
+            let dust_mass = self.bodies[d].mass;
+            let finished =
+                dust_mass * (1.0 - fraction) < self.bodies[a].mass * DUST_MIN_MASS_RATIO;
+            let dm = if finished { dust_mass } else { dust_mass * fraction };
+
+            let (dust, accretor) = (&self.bodies[d], &self.bodies[a]);
+            let total = accretor.mass + dm;
+            let velocity = (accretor.velocity.scale(accretor.mass) + dust.velocity.scale(dm))
+                .scale(1.0 / total);
+            // Weighted like the velocity so the next half-kick, which still
+            // uses these accelerations, keeps momentum
+            let acceleration = (accretor.acceleration.scale(accretor.mass)
+                + dust.acceleration.scale(dm))
+            .scale(1.0 / total);
+            let reach = self.separation(accretor.position, dust.position);
+            let position = accretor.position + reach.scale(dm / total);
+            let radius = (accretor.radius.powi(3) + dust.radius.powi(3) * dm / dust_mass).cbrt();
+
+            let accretor = &mut self.bodies[a];
+            accretor.mass = total;
+            accretor.radius = radius;
+            if !accretor.is_fixed {
+                accretor.velocity = velocity;
+                accretor.acceleration = acceleration;
+                accretor.position = position;
             }
-            for j in 0..n {
-                if i == j {
-                    continue;
-                }
-                let diff = self.bodies[j].position - self.bodies[i].position;
-                let dist_sq = diff.x * diff.x + diff.y * diff.y + diff.z * diff.z + self.softening * self.softening;
-                let dist = dist_sq.sqrt();
-                let force_mag = self.g * self.bodies[j].mass / dist_sq;
-                let dir = diff.scale(1.0 / dist);
-                accels[i] += dir.scale(force_mag);
+            self.collision_stats.mass_accreted += dm;
+
+            if finished {
+                absorbed[d] = true;
+                collisions.push(CollisionEvent {
+                    absorbed_id: self.bodies[d].id,
+                    survivor_id: self.bodies[a].id,
+                    position,
+                    combined_mass: total,
+                    sim_time: self.sim_time,
+                });
+                self.collision_stats.total_collisions += 1;
+                self.collision_stats.bodies_removed += 1;
+            } else {
+                let dust = &mut self.bodies[d];
+                dust.radius *= ((dust_mass - dm) / dust_mass).cbrt();
+                dust.mass = dust_mass - dm;
             }
         }
 
-        for (i, body) in self.bodies.iter_mut().enumerate() {
-            body.acceleration = accels[i];
-        }
+        let mut index = 0;
+        self.bodies.retain(|_| {
+            index += 1;
+            !absorbed[index - 1]
+        });
+        collisions
     }
 
-    fn compute_accelerations_gpu(&mut self, gpu: Arc<GpuGravity>) {
-        let positions: Vec<Vec3> = self.bodies.iter().map(|b| b.position).collect();
-        let masses: Vec<f64> = self.bodies.iter().map(|b| b.mass).collect();
-        let softening_sq = self.softening * self.softening;
-
-        let accels = gpu.compute_accelerations(&positions, &masses, self.g, softening_sq);
-
-        for (i, body) in self.bodies.iter_mut().enumerate() {
-            if !body.is_fixed {
-                body.acceleration = accels[i];
-            } else {
-                body.acceleration = Vec3::zero();
-            }
+    // Swept-sphere test: each body is assumed to move in a straight line from
+    // its start-of-substep position to where it is now, so a fast flyby that
+    // passes clean through another body between substeps still registers.
+    fn check_collisions(&mut self, start_positions: &[Vec3], dt: f64) -> Vec<CollisionEvent> {
+        let mut collisions = Vec::new();
+        // Soft contacts are handled as forces during the step
+        if self.collision.mode == CollisionMode::Soft {
+            return collisions;
         }
+        let candidates = self.collision_candidates(start_positions);
+        self.resolve_collisions(start_positions, dt, candidates, &mut collisions);
+        collisions
     }
 
-    fn compute_accelerations_barneshut(&mut self) {
+    // Merges or bounces every swept pair, trying only `candidates` when given
+    // and every pair otherwise
+    fn resolve_collisions(
+        &mut self,
+        start_positions: &[Vec3],
+        dt: f64,
+        candidates: Option<Vec<Vec<usize>>>,
+        collisions: &mut Vec<CollisionEvent>,
+    ) {
         let n = self.bodies.len();
-        let positions: Vec<Vec3> = self.bodies.iter().map(|b| b.position).collect();
-        let masses: Vec<f64> = self.bodies.iter().map(|b| b.mass).collect();
-
-        let tree = barneshut::build_octree(&positions, &masses);
-        let softening_sq = self.softening * self.softening;
+        // Pairs are visited in the same (i, j) order as a full double loop.
+        // Broad-phase candidates were found from the bodies as they were
+        // before any contact this substep, so once a contact moves or grows a
+        // body, that body is checked against everything after it instead.
+        let mut sweep = CollisionSweep {
+            candidates,
+            touched: vec![false; n],
+            touched_set: BTreeSet::new(),
+            absorbed: vec![false; n],
+        };
 
-        let mut accels = vec![Vec3::zero(); n];
-        for i in 0..n {
-            if self.bodies[i].is_fixed {
-                continue;
+        // A merged body can reach bodies the pass already went by, so passes
+        // repeat until nothing merges. After the first, only pairs with a
+        // body that grew in the previous pass can have changed.
+        let mut grown: Option<Vec<bool>> = None;
+        loop {
+            let grown_only = grown.as_deref();
+            let grew = self.collision_pass(start_positions, dt, &mut sweep, grown_only, collisions);
+            if !grew.contains(&true) {
+                break;
             }
-            accels[i] = tree.compute_acceleration(
-                &positions[i],
-                i,
-                self.g,
-                softening_sq,
-                self.theta,
-            );
+            grown = Some(grew);
         }
 
-        for (i, body) in self.bodies.iter_mut().enumerate() {
-            body.acceleration = accels[i];
+        // Remove absorbed bodies in reverse to preserve indices
+        let mut i = self.bodies.len();
+        while i > 0 {
+            i -= 1;
+            if sweep.absorbed[i] {
+                self.bodies.remove(i);
+            }
         }
     }
 
-    fn check_collisions(&mut self) -> Vec<CollisionEvent> {
-        let mut collisions = Vec::new();
-        let mut absorbed: Vec<bool> = vec![false; self.bodies.len()];
+    // Relative kinetic energy below the pair's binding energy at contact
+    // (centers one radius-sum apart), compared per unit reduced mass
+    fn pair_bound(&self, i: usize, j: usize) -> bool {
+        let (a, b) = (&self.bodies[i], &self.bodies[j]);
+        let rel_vel = b.velocity - a.velocity;
+        let contact = a.radius + b.radius;
+        0.5 * rel_vel.dot(&rel_vel) < self.g * (a.mass + b.mass).abs() / contact
+    }
 
+    // One sweep over the pairs, only those with a `grown` body when given.
+    // Returns which bodies survived a merge.
+    fn collision_pass(
+        &mut self,
+        start_positions: &[Vec3],
+        dt: f64,
+        sweep: &mut CollisionSweep,
+        grown: Option<&[bool]>,
+        collisions: &mut Vec<CollisionEvent>,
+    ) -> Vec<bool> {
         let n = self.bodies.len();
+        let mut grew = vec![false; n];
+        let CollisionSweep {
+            candidates,
+            touched,
+            touched_set,
+            absorbed,
+        } = sweep;
         for i in 0..n {
-            if absorbed[i] {
+            if absorbed[i] || self.bodies[i].lifetime.is_some() {
                 continue;
             }
-            for j in (i + 1)..n {
-                if absorbed[j] {
+            let mut j = i;
+            let candidates = candidates.as_deref();
+            while let Some(next) = next_partner(candidates, touched, touched_set, n, i, j) {
+                j = next;
+                // i itself may have just been swallowed by a heavier j
+                if absorbed[i] {
+                    break;
+                }
+                if absorbed[j] || self.bodies[j].lifetime.is_some() {
                     continue;
                 }
-                let diff = self.bodies[j].position - self.bodies[i].position;
-                let dist = (diff.x * diff.x + diff.y * diff.y + diff.z * diff.z).sqrt();
+                if grown.is_some_and(|grown| !grown[i] && !grown[j]) {
+                    continue;
+                }
+                // Opposite-sign pairs would merge into a near-zero mass and
+                // blow up the momentum division, so they pass through
+                if self.bodies[i].mass * self.bodies[j].mass < 0.0 {
+                    continue;
+                }
+                // Dust is left to `accrete_dust`
+                if self.collision.mode == CollisionMode::Accretion
+                    && (is_dust(&self.bodies[i], &self.bodies[j])
+                        || is_dust(&self.bodies[j], &self.bodies[i]))
+                {
+                    continue;
+                }
+                let start_diff = self.separation(start_positions[i], start_positions[j]);
+                let end_diff = self.separation(self.bodies[i].position, self.bodies[j].position);
                 let overlap = self.bodies[i].radius + self.bodies[j].radius;
 
-                if dist < overlap {
-                    let (survivor_idx, absorbed_idx) = if self.bodies[i].mass >= self.bodies[j].mass
-                    {
-                        (i, j)
-                    } else {
-                        (j, i)
-                    };
+                if !swept_overlap(&start_diff, &end_diff, overlap) {
+                    continue;
+                }
+                let protected = self.bodies[i].no_merge || self.bodies[j].no_merge;
+                if self.collision.mode == CollisionMode::Bounce || protected {
+                    for k in [i, j] {
+                        touched[k] = true;
+                        touched_set.insert(k);
+                    }
+                    // Tunnelled pairs no longer overlap; use the side they
+                    // approached from as the contact normal
+                    let normal = if end_diff.magnitude() < overlap { end_diff } else { start_diff };
+                    if self.resolve_contact(i, j, normal, dt) {
+                        self.collision_stats.total_collisions += 1;
+                    }
+                    continue;
+                }
 
-                    let m1 = self.bodies[survivor_idx].mass;
-                    let m2 = self.bodies[absorbed_idx].mass;
-                    let total_mass = m1 + m2;
-
-                    let new_velocity = Vec3::new(
-                        (m1 * self.bodies[survivor_idx].velocity.x
-                            + m2 * self.bodies[absorbed_idx].velocity.x)
-                            / total_mass,
-                        (m1 * self.bodies[survivor_idx].velocity.y
-                            + m2 * self.bodies[absorbed_idx].velocity.y)
-                            / total_mass,
-                        (m1 * self.bodies[survivor_idx].velocity.z
-                            + m2 * self.bodies[absorbed_idx].velocity.z)
-                            / total_mass,
-                    );
-
-                    let new_position = Vec3::new(
-                        (m1 * self.bodies[survivor_idx].position.x
-                            + m2 * self.bodies[absorbed_idx].position.x)
-                            / total_mass,
-                        (m1 * self.bodies[survivor_idx].position.y
-                            + m2 * self.bodies[absorbed_idx].position.y)
-                            / total_mass,
-                        (m1 * self.bodies[survivor_idx].position.z
-                            + m2 * self.bodies[absorbed_idx].position.z)
-                            / total_mass,
-                    );
-
-                    let r1 = self.bodies[survivor_idx].radius;
-                    let r2 = self.bodies[absorbed_idx].radius;
-                    let new_radius = (r1 * r1 * r1 + r2 * r2 * r2).cbrt();
-
-                    let collision = CollisionEvent {
-                        absorbed_id: self.bodies[absorbed_idx].id,
-                        survivor_id: self.bodies[survivor_idx].id,
-                        position: new_position,
-                        combined_mass: total_mass,
-                    };
+                if self.collision.require_bound_for_merge && !self.pair_bound(i, j) {
+                    continue;
+                }
 
-                    self.bodies[survivor_idx].mass = total_mass;
-                    self.bodies[survivor_idx].velocity = new_velocity;
-                    self.bodies[survivor_idx].position = new_position;
-                    self.bodies[survivor_idx].radius = new_radius;
-                    if self.bodies[absorbed_idx].is_fixed {
-                        self.bodies[survivor_idx].is_fixed = true;
-                    }
+                let (survivor_idx, absorbed_idx) = if self.bodies[i].mass.abs()
+                    >= self.bodies[j].mass.abs()
+                {
+                    (i, j)
+                } else {
+                    (j, i)
+                };
+
+                let m1 = self.bodies[survivor_idx].mass;
+                let m2 = self.bodies[absorbed_idx].mass;
+                let total_mass = m1 + m2;
+
+                let new_velocity = Vec3::new(
+                    (m1 * self.bodies[survivor_idx].velocity.x
+                        + m2 * self.bodies[absorbed_idx].velocity.x)
+                        / total_mass,
+                    (m1 * self.bodies[survivor_idx].velocity.y
+                        + m2 * self.bodies[absorbed_idx].velocity.y)
+                        / total_mass,
+                    (m1 * self.bodies[survivor_idx].velocity.z
+                        + m2 * self.bodies[absorbed_idx].velocity.z)
+                        / total_mass,
+                );
 
-                    absorbed[absorbed_idx] = true;
-                    collisions.push(collision);
+                // Measured from the survivor so a pair straddling a periodic
+                // boundary merges where they touch, not mid-box
+                let survivor_pos = self.bodies[survivor_idx].position;
+                let reach = self.separation(survivor_pos, self.bodies[absorbed_idx].position);
+                let new_position = survivor_pos + reach.scale(m2 / total_mass);
+
+                let r1 = self.bodies[survivor_idx].radius;
+                let r2 = self.bodies[absorbed_idx].radius;
+                let new_radius = (r1 * r1 * r1 + r2 * r2 * r2).cbrt();
+
+                // Angular momentum about the merged center: both spins plus
+                // the pair's orbital part, which a plain momentum merge
+                // would throw away. It all ends up as the survivor's spin.
+                let a = &self.bodies[survivor_idx];
+                let b = &self.bodies[absorbed_idx];
+                let angular_momentum = a.spin_angular_momentum()
+                    + b.spin_angular_momentum()
+                    + (a.position - new_position)
+                        .cross(&(a.velocity - new_velocity))
+                        .scale(m1)
+                    + (survivor_pos + reach - new_position)
+                        .cross(&(b.velocity - new_velocity))
+                        .scale(m2);
+                let new_inertia = CelestialBody::sphere_inertia(total_mass, new_radius);
+                let new_spin = if new_inertia != 0.0 {
+                    angular_momentum.scale(1.0 / new_inertia)
+                } else {
+                    Vec3::zero()
+                };
+
+                // Mass-weighted, so a speck barely tints what swallows it
+                let new_color = blend_colors(&a.color, m1.abs(), &b.color, m2.abs());
+
+                let collision = CollisionEvent {
+                    absorbed_id: self.bodies[absorbed_idx].id,
+                    survivor_id: self.bodies[survivor_idx].id,
+                    position: new_position,
+                    combined_mass: total_mass,
+                    sim_time: self.sim_time,
+                };
+
+                self.bodies[survivor_idx].mass = total_mass;
+                self.bodies[survivor_idx].velocity = new_velocity;
+                self.bodies[survivor_idx].position = new_position;
+                self.bodies[survivor_idx].radius = new_radius;
+                self.bodies[survivor_idx].angular_velocity = new_spin;
+                if let Some(color) = new_color {
+                    self.bodies[survivor_idx].color = color;
+                }
+                if self.bodies[absorbed_idx].is_fixed {
+                    self.bodies[survivor_idx].is_fixed = true;
                 }
+
+                absorbed[absorbed_idx] = true;
+                grew[survivor_idx] = true;
+                touched[survivor_idx] = true;
+                touched_set.insert(survivor_idx);
+                collisions.push(collision);
+
+                self.collision_stats.total_collisions += 1;
+                self.collision_stats.mass_accreted += m2;
+                self.collision_stats.bodies_removed += 1;
             }
         }
+        grew
+    }
 
-        // Remove absorbed bodies in reverse to preserve indices
-        let mut i = self.bodies.len();
-        while i > 0 {
-            i -= 1;
-            if absorbed[i] {
-                self.bodies.remove(i);
+    // Broad phase: bodies whose swept bounding boxes (start to end of the
+    // substep, padded by radius) share a cell of a uniform hash grid, listed
+    // under the lower index in ascending order. Cells are as wide as the
+    // largest box, so a box spans at most two cells per axis. None means
+    // every pair should be tried: small scenes, or a periodic box where
+    // neighbours can sit on opposite faces.
+    fn collision_candidates(&self, start_positions: &[Vec3]) -> Option<Vec<Vec<usize>>> {
+        let n = self.bodies.len();
+        if n < HASH_MIN_BODIES || self.periodic.is_some() {
+            return None;
+        }
+
+        let boxes: Vec<(Vec3, Vec3)> = self
+            .bodies
+            .iter()
+            .zip(start_positions.iter())
+            .map(|(body, start)| {
+                let (a, b) = (*start, body.position);
+                let pad = Vec3::new(body.radius, body.radius, body.radius);
+                let lo = Vec3::new(a.x.min(b.x), a.y.min(b.y), a.z.min(b.z));
+                let hi = Vec3::new(a.x.max(b.x), a.y.max(b.y), a.z.max(b.z));
+                (lo - pad, hi + pad)
+            })
+            .collect();
+        let cell = boxes
+            .iter()
+            .map(|(lo, hi)| (hi.x - lo.x).max(hi.y - lo.y).max(hi.z - lo.z))
+            .fold(0.0, f64::max);
+        if !cell.is_finite() || cell <= 0.0 {
+            return None;
+        }
+
+        let cell_of = |v: f64| (v / cell).floor() as i64;
+        let mut grid: HashMap<(i64, i64, i64), Vec<usize>> = HashMap::new();
+        for (index, (lo, hi)) in boxes.iter().enumerate() {
+            for x in cell_of(lo.x)..=cell_of(hi.x) {
+                for y in cell_of(lo.y)..=cell_of(hi.y) {
+                    for z in cell_of(lo.z)..=cell_of(hi.z) {
+                        grid.entry((x, y, z)).or_default().push(index);
+                    }
+                }
             }
         }
 
-        collisions
+        let boxes_meet = |a: usize, b: usize| {
+            let ((alo, ahi), (blo, bhi)) = (boxes[a], boxes[b]);
+            let spans_meet = |a0: f64, a1: f64, b0: f64, b1: f64| a0 <= b1 && b0 <= a1;
+            spans_meet(alo.x, ahi.x, blo.x, bhi.x)
+                && spans_meet(alo.y, ahi.y, blo.y, bhi.y)
+                && spans_meet(alo.z, ahi.z, blo.z, bhi.z)
+        };
+        // Each cell lists its bodies in ascending order, so pairs come out
+        // with the lower index first
+        let mut pairs: Vec<(usize, usize)> = grid
+            .par_iter()
+            .flat_map_iter(|(_, members)| {
+                members.iter().enumerate().flat_map(move |(k, &a)| {
+                    members[k + 1..]
+                        .iter()
+                        .filter(move |&&b| boxes_meet(a, b))
+                        .map(move |&b| (a, b))
+                })
+            })
+            .collect();
+        pairs.par_sort_unstable();
+        pairs.dedup();
+
+        let mut partners = vec![Vec::new(); n];
+        for (a, b) in pairs {
+            partners[a].push(b);
+        }
+        Some(partners)
     }
 
-    pub fn predict_orbit(&self, body_id: u32, steps: u32) -> Vec<Vec3> {
-        let mut pred = SimulationState {
-            bodies: self.bodies.clone(),
+    // The live configuration with no scene attached: no bodies, the clock at
+    // zero, and every cache, history and UI link reset. Settings are copied;
+    // a new field goes under whichever of the two it is.
+    fn detached(&self) -> SimulationState {
+        SimulationState {
+            bodies: Vec::new(),
             tick: 0,
+            sim_time: 0.0,
             dt: self.dt,
             g: self.g,
             softening: self.softening,
             paused: false,
             speed_multiplier: 1.0,
-            next_id: self.next_id,
+            step_accumulator: 0.0,
+            next_id: 0,
             theta: self.theta,
+            tree_bounds: None,
+            integrator: self.integrator,
+            softening_mode: self.softening_mode,
+            refit_softening: false,
+            softening_kernel: self.softening_kernel,
+            smooth_accretion: self.smooth_accretion,
+            force_exponent: self.force_exponent,
+            relativistic: self.relativistic,
+            light_speed: self.light_speed,
+            collision: self.collision,
+            collision_stats: CollisionStats::default(),
+            max_bodies: self.max_bodies,
+            overflow_policy: self.overflow_policy,
+            allow_negative_mass: self.allow_negative_mass,
+            ghost: None,
+            focus_body: None,
+            focus_velocity: false,
+            trail_frame: TrailFrame::World,
+            trail_origin: None,
+            collapse_snapshot: None,
+            snapshot_interval: 0,
+            snapshots: VecDeque::new(),
+            frame_lod: None,
+            lod: LodState::default(),
+            step_hook: None,
+            nan_guard: self.nan_guard,
+            period_trails: self.period_trails,
+            periodic: self.periodic,
+            orbit_info: self.orbit_info,
+            drag: self.drag,
+            settle_ticks: self.settle_ticks,
+            spawn_exhaust: self.spawn_exhaust,
+            exhaust_lifetime: self.exhaust_lifetime,
+            max_speed: self.max_speed,
+            speed_clamps: Vec::new(),
+            rng: self.rng.clone(),
+            numerical_errors: Vec::new(),
+            telemetry: Telemetry::default(),
+            speed_range: None,
+            apsis_tracks: HashMap::new(),
+            brute_max: self.brute_max,
+            gpu_min: self.gpu_min,
+            solver: self.solver,
+            pm_grid: self.pm_grid,
+            substeps: self.substeps,
+            gpu_precise: self.gpu_precise,
+            use_gpu: self.use_gpu,
             gpu: self.gpu.clone(),
-        };
+            pool: self.pool.clone(),
+            view: serde_json::Value::Null,
+        }
+    }
 
+    // Throwaway copy of the live state for look-ahead integration
+    fn prediction_state(&self) -> SimulationState {
+        let mut pred = self.detached();
+        pred.bodies = self.bodies.clone();
         for body in pred.bodies.iter_mut() {
             body.trail.clear();
         }
+        pred.sim_time = self.sim_time;
+        pred.next_id = self.next_id;
+        pred.tree_bounds = self.tree_bounds;
+        pred
+    }
+
+    // Empty state carrying the live configuration, for building a new scene
+    // without holding the lock. Generators clear and fill it, then
+    // `install_staged` swaps the scene in.
+    pub fn staging_state(&self) -> SimulationState {
+        self.detached()
+    }
+
+    // Replaces the scene with a generated one: its bodies, ids, clock and
+    // rng, and the per-scenario settings a loader picks (dt, G, theta,
+    // integrator, softening). Everything else keeps its live value, since the
+    // user may have changed it while the scene was being built, and the
+    // accelerations are recomputed under those settings.
+    pub fn install_staged(&mut self, staged: SimulationState) {
+        self.clear();
+        self.bodies = staged.bodies;
+        self.next_id = staged.next_id;
+        self.tick = staged.tick;
+        self.sim_time = staged.sim_time;
+        self.rng = staged.rng;
+        self.dt = staged.dt;
+        self.g = staged.g;
+        self.apply_scenario_settings(staged.theta, staged.integrator, staged.softening);
+        self.prime_accelerations();
+    }
 
+    // Called through `on_pool` so predictions share the physics threads
+    fn run_prediction(
+        pred: &mut SimulationState,
+        body_id: u32,
+        steps: u32,
+        maneuvers: &[ManeuverNode],
+    ) -> Vec<Vec3> {
         let mut path = Vec::with_capacity(steps as usize);
+        // Nodes already in the past when the prediction starts are dropped
+        let mut pending: Vec<ManeuverNode> =
+            maneuvers.iter().copied().filter(|m| m.time >= pred.sim_time).collect();
+        pending.sort_by(|a, b| a.time.total_cmp(&b.time));
+        let mut pending = pending.into_iter().peekable();
+        let mut time = pred.sim_time;
 
         for _ in 0..steps {
-            pred.step_verlet(pred.dt);
+            while let Some(node) = pending.next_if(|m| m.time <= time) {
+                if let Some(body) = pred.find_body_mut(body_id) {
+                    body.apply_maneuver(node.delta_v);
+                }
+            }
+            pred.purge_non_finite();
+            pred.integrate(pred.dt);
+            time += pred.dt;
             if let Some(body) = pred.find_body(body_id) {
                 path.push(body.position);
             } else {
@@ -360,22 +3414,146 @@ impl SimulationState {
         path
     }
 
-    fn compute_energies(&self) -> EnergyData {
+    // With `include_thrust` the copy keeps each spacecraft's current thrust and
+    // burns its own fuel, so the path bends exactly as long as the live burn
+    // could last. Without it every body coasts. `maneuvers` are burns
+    // scheduled for the predicted body; they draw on the same copied fuel.
+    pub fn predict_orbit(
+        &self,
+        body_id: u32,
+        steps: u32,
+        include_thrust: bool,
+        maneuvers: &[ManeuverNode],
+    ) -> Vec<Vec3> {
+        let mut pred = self.prediction_state();
+        pred.on_pool(|pred| {
+            if !include_thrust {
+                for body in pred.bodies.iter_mut() {
+                    body.thrust = Vec3::zero();
+                }
+                pred.refresh_accelerations();
+            }
+            Self::run_prediction(pred, body_id, steps, maneuvers)
+        })
+    }
+
+    // Same as `predict_orbit` but starting from the snapshot taken at `tick`,
+    // with the current settings. None if there is no such snapshot or the
+    // body did not exist at that point.
+    pub fn predict_orbit_from(
+        &self,
+        tick: u64,
+        body_id: u32,
+        steps: u32,
+        include_thrust: bool,
+    ) -> Option<Vec<Vec3>> {
+        let snap = self.snapshots.iter().find(|snap| snap.tick == tick)?;
+        snap.bodies.iter().find(|b| b.id == body_id)?;
+        let mut pred = self.prediction_state();
+        pred.bodies = snap.bodies.clone();
+        pred.sim_time = snap.sim_time;
+        if !include_thrust {
+            for body in pred.bodies.iter_mut() {
+                body.thrust = Vec3::zero();
+            }
+        }
+        Some(pred.on_pool(|pred| {
+            pred.refresh_accelerations();
+            Self::run_prediction(pred, body_id, steps, &[])
+        }))
+    }
+
+    // Predicts the ghost's path through the current system. The ghost rides
+    // along as a test particle so the real bodies follow exactly the paths
+    // they would without it.
+    pub fn predict_ghost_orbit(&self, steps: u32) -> Vec<Vec3> {
+        let Some(ghost) = &self.ghost else {
+            return Vec::new();
+        };
+        let mut pred = self.prediction_state();
+        let mut probe = ghost.clone();
+        probe.id = GHOST_ID;
+        probe.is_test_particle = true;
+        pred.bodies.push(probe);
+        pred.on_pool(|pred| {
+            pred.refresh_accelerations();
+            Self::run_prediction(pred, GHOST_ID, steps, &[])
+        })
+    }
+
+    // Runs the current scene forward on a throwaway copy with candidate G and
+    // softening and reports how far total energy wandered. The live state is
+    // untouched, so the UI can check before committing a change.
+    pub fn validate_stability(
+        &self,
+        g: f64,
+        softening: f64,
+        steps: u32,
+    ) -> Result<StabilityReport, String> {
+        let mut pred = self.prediction_state();
+        pred.g = check_gravity(g)?;
+        pred.softening = check_softening(softening)?;
+        let (e0, e1) = pred.on_pool(|pred| {
+            pred.refresh_accelerations();
+            let e0 = pred.compute_energies().total;
+            for _ in 0..steps {
+                pred.purge_non_finite();
+                pred.integrate(pred.dt);
+            }
+            (e0, pred.compute_energies().total)
+        });
+
+        let drift = if e0.abs() > 0.0 {
+            ((e1 - e0) / e0).abs()
+        } else {
+            (e1 - e0).abs()
+        };
+        // Losing a body to the NaN guard is instability no matter the drift
+        let stable = drift.is_finite()
+            && drift < MAX_STABLE_DRIFT
+            && pred.numerical_errors.is_empty();
+        Ok(StabilityReport { stable, drift })
+    }
+
+    // Turns the ghost into a real body, returning its new id
+    pub fn commit_ghost(&mut self) -> Result<u32, String> {
+        if self.ghost.is_none() {
+            return Err("No ghost body to commit".to_string());
+        }
+        if self.make_room(1, None) == 0 {
+            return Err(format!("Body limit of {} reached", self.max_bodies));
+        }
+        let mut body = self.ghost.take().unwrap();
+        body.id = self.allocate_id();
+        Ok(self.add_body(body))
+    }
+
+    pub fn compute_energies(&self) -> EnergyData {
         let n = self.bodies.len();
         let mut ke = 0.0;
         let mut pe = 0.0;
+        let mut free_ke = 0.0;
+        let mut free_pe = 0.0;
 
         for body in &self.bodies {
             let v2 = body.velocity.x * body.velocity.x + body.velocity.y * body.velocity.y + body.velocity.z * body.velocity.z;
             ke += 0.5 * body.mass * v2;
+            if !body.is_fixed {
+                free_ke += 0.5 * body.mass * v2;
+            }
         }
 
         for i in 0..n {
             for j in (i + 1)..n {
-                let diff = self.bodies[j].position - self.bodies[i].position;
+                let diff = self.separation(self.bodies[i].position, self.bodies[j].position);
                 let dist = (diff.x * diff.x + diff.y * diff.y + diff.z * diff.z).sqrt();
                 if dist > 0.001 {
-                    pe -= self.g * self.bodies[i].mass * self.bodies[j].mass / dist;
+                    let gmm = self.g * self.bodies[i].mass * self.bodies[j].mass;
+                    let term = gmm * self.potential_shape(dist);
+                    pe -= term;
+                    if !(self.bodies[i].is_fixed && self.bodies[j].is_fixed) {
+                        free_pe -= term;
+                    }
                 }
             }
         }
@@ -384,26 +3562,595 @@ impl SimulationState {
             kinetic: ke,
             potential: pe,
             total: ke + pe,
+            virial_ratio: if pe != 0.0 { 2.0 * ke / pe.abs() } else { 0.0 },
+            free_kinetic: free_ke,
+            free_potential: free_pe,
+            free_total: free_ke + free_pe,
+        }
+    }
+
+    // -U / (G m1 m2) for the configured force law, zero at infinity except
+    // for the logarithmic 1/r case
+    fn potential_shape(&self, dist: f64) -> f64 {
+        let p = self.force_exponent;
+        if p == 2.0 {
+            1.0 / dist
+        } else if (p - 1.0).abs() < 1e-9 {
+            -dist.ln()
+        } else {
+            dist.powf(1.0 - p) / (p - 1.0)
+        }
+    }
+
+    pub fn scenario_loaded(&self, name: &str) -> ScenarioLoadedEvent {
+        ScenarioLoadedEvent {
+            name: name.to_string(),
+            body_count: self.bodies.len(),
+            bounds: self.camera_bounds(),
+        }
+    }
+
+    // Center of the axis-aligned box around visible bodies, padded by their radii
+    pub fn camera_bounds(&self) -> CameraBounds {
+        let visible: Vec<&CelestialBody> = self.bodies.iter().filter(|b| !b.hidden).collect();
+        if visible.is_empty() {
+            return CameraBounds { center: Vec3::zero(), radius: 0.0 };
         }
+
+        let mut min = visible[0].position;
+        let mut max = visible[0].position;
+        for b in &visible {
+            min = Vec3::new(min.x.min(b.position.x), min.y.min(b.position.y), min.z.min(b.position.z));
+            max = Vec3::new(max.x.max(b.position.x), max.y.max(b.position.y), max.z.max(b.position.z));
+        }
+        let center = (min + max).scale(0.5);
+        let radius = visible
+            .iter()
+            .map(|b| (b.position - center).magnitude() + b.radius)
+            .fold(0.0, f64::max);
+        CameraBounds { center, radius }
     }
 
     pub fn to_frame(&self) -> SimulationFrame {
+        let mut bodies = self.bodies.clone();
+        let mut ghost = self.ghost.clone();
+
+        // A focus body that has since been merged away falls back to world
+        let focus = self.focus_body.and_then(|id| self.find_body(id));
+        let (focus_body, origin, velocity) = match focus {
+            Some(f) => {
+                let velocity = if self.focus_velocity { f.velocity } else { Vec3::zero() };
+                (Some(f.id), f.position, velocity)
+            }
+            None => (None, Vec3::zero(), Vec3::zero()),
+        };
+        // World-frame trails are shifted rigidly by the focus body's current
+        // position, so they keep their world-frame shape. Focus-frame trails
+        // are placed around their origin body's current position instead.
+        let anchor = self.trail_origin.and_then(|id| self.find_body(id)).map(|b| b.position);
+        if focus_body.is_some() || anchor.is_some() {
+            let anchor = anchor.unwrap_or_default();
+            for body in bodies.iter_mut().chain(ghost.iter_mut()) {
+                body.translate(anchor - origin);
+                body.position = body.position - anchor;
+                body.velocity = body.velocity - velocity;
+            }
+        }
+
         SimulationFrame {
-            bodies: self.bodies.clone(),
+            bodies,
             tick: self.tick,
+            sim_time: self.sim_time,
             paused: self.paused,
             speed_multiplier: self.speed_multiplier,
             energy: self.compute_energies(),
+            ghost,
+            focus_body,
+            frame_origin: origin,
+            frame_velocity: velocity,
+            telemetry: self.telemetry.clone(),
+            orbits: self.orbit_info.then(|| self.body_orbits()),
+            held: Vec::new(),
         }
     }
 
     pub fn prime_accelerations(&mut self) {
-        self.compute_accelerations();
+        let refit = std::mem::take(&mut self.refit_softening);
+        if refit && self.softening_mode == SofteningMode::Auto {
+            self.fit_softening();
+        }
+        self.on_pool(|sim| {
+            sim.purge_non_finite();
+            sim.refresh_accelerations();
+        });
+    }
+
+    fn purge_non_finite(&mut self) {
+        if !self.nan_guard {
+            return;
+        }
+        let is_finite = |v: &Vec3| v.x.is_finite() && v.y.is_finite() && v.z.is_finite();
+        let tick = self.tick;
+        let sim_time = self.sim_time;
+        let errors = &mut self.numerical_errors;
+        self.bodies.retain(|b| {
+            let ok = is_finite(&b.position)
+                && is_finite(&b.velocity)
+                && is_finite(&b.acceleration)
+                && b.mass.is_finite();
+            if !ok {
+                errors.push(NumericalErrorEvent {
+                    body_id: b.id,
+                    name: b.name.clone(),
+                    tick,
+                    sim_time,
+                });
+            }
+            ok
+        });
+    }
+
+    pub fn take_numerical_errors(&mut self) -> Vec<NumericalErrorEvent> {
+        std::mem::take(&mut self.numerical_errors)
+    }
+
+    // Switches settling on for `ticks` ticks; zero ticks or zero drag turns it
+    // off now
+    pub fn settle(&mut self, drag: f64, ticks: u64) -> Result<(), String> {
+        if !drag.is_finite() || drag < 0.0 {
+            return Err(format!("drag must be non-negative, got {}", drag));
+        }
+        self.drag = drag;
+        self.settle_ticks = if drag > 0.0 { ticks } else { 0 };
+        Ok(())
+    }
+
+    // Pulls each free body's velocity relative to its primary toward the
+    // circular velocity there: speed sqrt(r a_r) from the radial part of the
+    // current (relative) acceleration, so halos and neighbours count, along
+    // the body's own direction of travel about the primary
+    fn apply_settling_drag(&mut self, dt: f64) {
+        if self.settle_ticks == 0 || self.drag <= 0.0 {
+            return;
+        }
+        let keep = (-self.drag * dt).exp();
+        let targets: Vec<Option<Vec3>> = (0..self.bodies.len())
+            .into_par_iter()
+            .map(|i| {
+                let body = &self.bodies[i];
+                if body.is_fixed {
+                    return None;
+                }
+                let primary = &self.bodies[self.primary_of(i)?];
+                let rel = self.separation(primary.position, body.position);
+                let r = rel.magnitude();
+                let rel_vel = body.velocity - primary.velocity;
+                let radial = rel.scale(1.0 / r);
+                let tangent = rel_vel - radial.scale(rel_vel.dot(&radial));
+                let inward = -(body.acceleration - primary.acceleration).dot(&radial);
+                let tangent_speed = tangent.magnitude();
+                if !(r > 0.0 && inward > 0.0 && tangent_speed > 0.0) {
+                    return None;
+                }
+                let circular = tangent.scale((r * inward).sqrt() / tangent_speed);
+                Some(primary.velocity + circular)
+            })
+            .collect();
+        for (body, target) in self.bodies.iter_mut().zip(targets) {
+            if let Some(target) = target {
+                body.velocity = target + (body.velocity - target).scale(keep);
+            }
+        }
+    }
+
+    // Scales runaway velocities back to `max_speed`, keeping their direction.
+    // `acceleration` is left alone so the next step still sees the real pull.
+    fn clamp_speeds(&mut self) {
+        let Some(max_speed) = self.max_speed else {
+            return;
+        };
+        for body in self.bodies.iter_mut() {
+            let speed = body.velocity.magnitude();
+            if speed <= max_speed {
+                continue;
+            }
+            body.velocity = body.velocity.scale(max_speed / speed);
+            let reported = self
+                .speed_clamps
+                .iter()
+                .any(|e| e.body_id == body.id && e.tick == self.tick);
+            if !reported {
+                self.speed_clamps.push(SpeedClampedEvent {
+                    body_id: body.id,
+                    name: body.name.clone(),
+                    speed,
+                    max_speed,
+                    tick: self.tick,
+                    sim_time: self.sim_time,
+                });
+            }
+        }
+    }
+
+    // Restarts the running range from the trails as they stand now
+    pub fn reset_speed_range(&mut self) -> Option<SpeedRange> {
+        self.speed_range = self
+            .bodies
+            .iter()
+            .flat_map(|b| b.trail.iter())
+            .fold(None, |range, point| SpeedRange::include(range, point.speed));
+        self.speed_range
+    }
+
+    pub fn take_speed_clamps(&mut self) -> Vec<SpeedClampedEvent> {
+        std::mem::take(&mut self.speed_clamps)
     }
 
     pub fn clear(&mut self) {
         self.bodies.clear();
         self.tick = 0;
+        self.sim_time = 0.0;
         self.next_id = 0;
+        self.collision_stats = CollisionStats::default();
+        self.speed_range = None;
+        self.apsis_tracks.clear();
+        self.settle_ticks = 0;
+        self.view = serde_json::Value::Null;
+        self.ghost = None;
+        // Ids restart from zero, so an old focus id would latch onto a
+        // different body
+        self.focus_body = None;
+        self.trail_origin = None;
+        self.collapse_snapshot = None;
+        self.snapshots.clear();
+        self.lod = LodState::default();
+        self.tree_bounds = None;
+        self.refit_softening = true;
+    }
+}
+
+// Bookkeeping shared by the passes `check_collisions` makes in one substep
+struct CollisionSweep {
+    candidates: Option<Vec<Vec<usize>>>,
+    touched: Vec<bool>,
+    touched_set: BTreeSet<usize>,
+    absorbed: Vec<bool>,
+}
+
+// Next body after `after` to test against `i`: the next broad-phase
+// candidate or touched body, whichever comes first, or simply the next index
+// when there are no candidates or `i` itself has been touched
+fn next_partner(
+    candidates: Option<&[Vec<usize>]>,
+    touched: &[bool],
+    touched_set: &BTreeSet<usize>,
+    n: usize,
+    i: usize,
+    after: usize,
+) -> Option<usize> {
+    let following = after + 1;
+    let Some(candidates) = candidates.filter(|_| !touched[i]) else {
+        return (following < n).then_some(following);
+    };
+    let list = &candidates[i];
+    let candidate = list.get(list.partition_point(|&k| k <= after)).copied();
+    let touched_next = touched_set.range(following..).next().copied();
+    match (candidate, touched_next) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    }
+}
+
+fn offset_all(base: &[Vec3], rate: &[Vec3], h: f64) -> Vec<Vec3> {
+    base.iter()
+        .zip(rate.iter())
+        .map(|(b, r)| *b + r.scale(h))
+        .collect()
+}
+
+// Whether `dust` counts as dust to `accretor` in accretion mode. Exhaust
+// and no-merge bodies never take part.
+fn is_dust(dust: &CelestialBody, accretor: &CelestialBody) -> bool {
+    let excluded = |b: &CelestialBody| b.no_merge || b.lifetime.is_some();
+    dust.mass > 0.0
+        && dust.mass < accretor.mass * DUST_MASS_RATIO
+        && !excluded(dust)
+        && !excluded(accretor)
+}
+
+// Whether the separation d(t) = start + (end - start) * t comes within
+// `reach` for some t in [0, 1]: the smaller root of |d(t)|^2 = reach^2.
+fn swept_overlap(start: &Vec3, end: &Vec3, reach: f64) -> bool {
+    let c = start.dot(start) - reach * reach;
+    if c < 0.0 {
+        return true;
+    }
+    let motion = *end - *start;
+    let a = motion.dot(&motion);
+    if a == 0.0 {
+        return false;
+    }
+    let b = 2.0 * start.dot(&motion);
+    let disc = b * b - 4.0 * a * c;
+    if disc < 0.0 {
+        return false;
+    }
+    let t = (-b - disc.sqrt()) / (2.0 * a);
+    (0.0..=1.0).contains(&t)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Free bodies scattered through a cube, reproducible from the seed
+    fn random_scene(n: usize, seed: u64) -> SimulationState {
+        let mut state = SimulationState::new();
+        let mut rng = SimRng::from_seed(seed);
+        for _ in 0..n {
+            let id = state.allocate_id();
+            let mut coord = || rng.random_range(-500.0..500.0);
+            let position = Vec3::new(coord(), coord(), coord());
+            let velocity = Vec3::new(coord(), coord(), coord()).scale(0.01);
+            let mass = rng.random_range(1.0..100.0);
+            let body = CelestialBody::new(id, "p", position, velocity, mass, 1.0, "#fff", false);
+            state.bodies.push(body);
+        }
+        state
+    }
+
+    fn bits(accels: &[Vec3]) -> Vec<[u64; 3]> {
+        accels
+            .iter()
+            .map(|a| [a.x.to_bits(), a.y.to_bits(), a.z.to_bits()])
+            .collect()
+    }
+
+    #[test]
+    fn settings_are_checked_at_their_boundaries() {
+        let tiny = f64::MIN_POSITIVE;
+        assert!(check_dt(0.0).is_err());
+        assert!(check_dt(-tiny).is_err());
+        assert_eq!(check_dt(tiny), Ok(tiny));
+
+        assert!(check_softening(-tiny).is_err());
+        assert_eq!(check_softening(0.0), Ok(0.0));
+
+        assert!(check_theta(-tiny).is_err());
+        assert_eq!(check_theta(0.0), Ok(0.0));
+
+        assert!(check_speed(0.0).is_err());
+        assert!(check_speed(-tiny).is_err());
+        assert_eq!(check_speed(tiny), Ok(tiny));
+
+        assert_eq!(check_gravity(-1.0), Ok(-1.0));
+        assert_eq!(check_gravity(0.0), Ok(0.0));
+        for bad in [f64::NAN, f64::INFINITY, f64::NEG_INFINITY] {
+            assert!(check_gravity(bad).is_err());
+            assert!(check_dt(bad).is_err());
+            assert!(check_softening(bad).is_err());
+            assert!(check_theta(bad).is_err());
+            assert!(check_speed(bad).is_err());
+        }
+    }
+
+    #[test]
+    fn builder_rejects_what_the_setters_would() {
+        assert!(SimulationState::builder().dt(0.0).build().is_err());
+        assert!(SimulationState::builder().g(f64::NAN).build().is_err());
+        assert!(SimulationState::builder().softening(-1.0).build().is_err());
+        let state = SimulationState::builder().dt(0.5).softening(0.0).build().unwrap();
+        assert_eq!((state.dt, state.softening), (0.5, 0.0));
+    }
+
+    #[test]
+    fn triple_pileup_merges_into_one_body_in_any_order() {
+        let bodies = [
+            (1.0, Vec3::new(0.0, 0.0, 0.0), Vec3::new(3.0, 0.0, 0.0)),
+            (2.0, Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, -1.0, 0.5)),
+            (3.0, Vec3::new(0.0, 1.0, 0.0), Vec3::new(-1.0, 2.0, 0.0)),
+        ];
+        let orders = [[0, 1, 2], [0, 2, 1], [1, 0, 2], [1, 2, 0], [2, 0, 1], [2, 1, 0]];
+        for order in orders {
+            let mut state = SimulationState::new();
+            for k in order {
+                let (mass, position, velocity) = bodies[k];
+                let id = state.allocate_id();
+                let radius = 5.0;
+                let body =
+                    CelestialBody::new(id, "b", position, velocity, mass, radius, "#fff", false);
+                state.bodies.push(body);
+            }
+            let (momentum, center) = (state.total_momentum(), state.barycenter());
+            let start: Vec<Vec3> = state.bodies.iter().map(|b| b.position).collect();
+
+            let events = state.check_collisions(&start, state.dt);
+            assert_eq!(events.len(), 2, "order {order:?}");
+            assert_eq!(state.bodies.len(), 1, "order {order:?}");
+            assert!((state.bodies[0].mass - 6.0).abs() < 1e-12, "order {order:?}");
+            let drift = (state.total_momentum() - momentum).magnitude();
+            assert!(drift < 1e-12, "order {order:?}: momentum off by {drift}");
+            let shift = (state.bodies[0].position - center).magnitude();
+            assert!(shift < 1e-12, "order {order:?}: merged {shift} off the barycenter");
+        }
+    }
+
+    #[test]
+    fn hashed_collisions_match_every_pair() {
+        for mode in [CollisionMode::Merge, CollisionMode::Bounce] {
+            // Bodies swept a long way through a crowded box, so plenty touch
+            let scene = || {
+                let mut state = random_scene(600, 635);
+                state.collision.mode = mode;
+                for body in &mut state.bodies {
+                    body.radius = 4.0 + body.mass / 10.0;
+                    body.position += body.velocity.scale(600.0);
+                }
+                state
+            };
+            let (mut hashed, mut naive) = (scene(), scene());
+            let start: Vec<Vec3> =
+                random_scene(600, 635).bodies.iter().map(|b| b.position).collect();
+            assert!(hashed.collision_candidates(&start).is_some());
+
+            let from_hash = hashed.check_collisions(&start, hashed.dt);
+            let mut from_pairs = Vec::new();
+            naive.resolve_collisions(&start, naive.dt, None, &mut from_pairs);
+
+            let events = |events: &[CollisionEvent]| -> Vec<(u32, u32, u64)> {
+                let key = |e: &CollisionEvent| (e.absorbed_id, e.survivor_id, e.combined_mass);
+                events.iter().map(key).map(|(a, s, m)| (a, s, m.to_bits())).collect()
+            };
+            assert_eq!(events(&from_hash), events(&from_pairs));
+            let total = |state: &SimulationState| state.collision_stats.total_collisions;
+            assert_eq!(total(&hashed), total(&naive));
+            assert!(total(&hashed) > 0);
+            let ids = |state: &SimulationState| -> Vec<u32> {
+                state.bodies.iter().map(|b| b.id).collect()
+            };
+            let positions = |state: &SimulationState| -> Vec<Vec3> {
+                state.bodies.iter().map(|b| b.position).collect()
+            };
+            let velocities = |state: &SimulationState| -> Vec<Vec3> {
+                state.bodies.iter().map(|b| b.velocity).collect()
+            };
+            assert_eq!(ids(&hashed), ids(&naive));
+            assert_eq!(bits(&positions(&hashed)), bits(&positions(&naive)));
+            assert_eq!(bits(&velocities(&hashed)), bits(&velocities(&naive)));
+        }
+    }
+
+    #[test]
+    fn parallel_tree_walk_matches_serial() {
+        let state = random_scene(2000, 590);
+        let positions: Vec<Vec3> = state.bodies.iter().map(|b| b.position).collect();
+        let masses: Vec<f64> = state.bodies.iter().map(|b| b.mass).collect();
+        let attrs: Vec<BodyAttributes> = state.bodies.iter().map(BodyAttributes::of).collect();
+        let on_threads = |threads: usize| {
+            let pool = rayon::ThreadPoolBuilder::new().num_threads(threads).build().unwrap();
+            pool.install(|| state.tree_accelerations(&positions, &masses, Some(&attrs)).0)
+        };
+
+        let serial = bits(&on_threads(1));
+        for threads in [2, 4, 8] {
+            assert_eq!(bits(&on_threads(threads)), serial, "{} threads", threads);
+            assert_eq!(bits(&on_threads(threads)), serial, "{} threads, rerun", threads);
+        }
+    }
+
+    #[test]
+    fn maneuver_nodes_fire_on_schedule_within_fuel() {
+        let mut state = SimulationState::new();
+        state.g = 0.0;
+        let id = state.allocate_id();
+        let velocity = Vec3::new(1.0, 0.0, 0.0);
+        let mut ship = CelestialBody::new(id, "s", Vec3::zero(), velocity, 2.0, 1.0, "#fff", false);
+        ship.body_type = BodyType::Spacecraft;
+        ship.fuel = 1.0;
+        state.bodies.push(ship);
+        state.prime_accelerations();
+
+        let dt = state.dt;
+        let at = state.sim_time + 10.0 * dt;
+        let burn = |delta_y: f64, time: f64| {
+            let node = ManeuverNode {
+                time,
+                delta_v: Vec3::new(0.0, delta_y, 0.0),
+            };
+            state.predict_orbit(id, 20, false, &[node])
+        };
+
+        // Costs 0.1 * 2 * 1 of the 1.0 fuel: delivered in full from step 10
+        let path = burn(1.0, at);
+        assert_eq!(path[9].y, 0.0);
+        assert!((path[19].y - 10.0 * dt).abs() < 1e-9);
+        // Would cost 2.0: only half the tank's worth is delivered
+        let path = burn(10.0, at);
+        assert!((path[19].y - 50.0 * dt).abs() < 1e-9);
+        // Already in the past
+        let path = burn(1.0, state.sim_time - 1.0);
+        assert_eq!(path[19].y, 0.0);
+        assert_eq!(state.bodies[0].fuel, 1.0);
+    }
+
+    fn angular_momentum(state: &SimulationState) -> Vec3 {
+        state.bodies.iter().fold(Vec3::zero(), |sum, b| {
+            sum + b.spin_angular_momentum() + b.position.cross(&b.velocity).scale(b.mass)
+        })
+    }
+
+    #[test]
+    fn merge_turns_lost_orbital_angular_momentum_into_spin() {
+        let mut state = SimulationState::new();
+        state.g = 0.0;
+        let pair = [
+            (Vec3::zero(), Vec3::new(1.0, 0.0, 0.0), 10.0, 5.0),
+            (Vec3::new(3.0, 4.0, 0.0), Vec3::new(-2.0, 1.0, 0.0), 5.0, 2.0),
+        ];
+        for (position, velocity, mass, radius) in pair {
+            let id = state.allocate_id();
+            let body = CelestialBody::new(id, "b", position, velocity, mass, radius, "#fff", false);
+            state.bodies.push(body);
+        }
+        let before = angular_momentum(&state);
+        state.step_once();
+        assert_eq!(state.bodies.len(), 1);
+
+        // What a merge keeping only linear momentum would have been left with
+        let merged = &state.bodies[0];
+        let translational = merged.position.cross(&merged.velocity).scale(merged.mass);
+        let dropped = before - translational;
+        assert!(dropped.magnitude() > 0.1 * before.magnitude(), "dropped {:?}", dropped);
+        let spin = merged.spin_angular_momentum();
+        assert!((spin - dropped).magnitude() <= 1e-9 * before.magnitude());
+        assert!((angular_momentum(&state) - before).magnitude() <= 1e-9 * before.magnitude());
+    }
+
+    #[test]
+    fn evicting_never_removes_the_body_being_orbited() {
+        let mut state = random_scene(4, 589);
+        state.max_bodies = 4;
+        state.overflow_policy = OverflowPolicy::EvictLightest;
+        let lightest = state.bodies.iter().min_by(|a, b| a.mass.total_cmp(&b.mass)).unwrap().id;
+
+        let disc = state.add_disc(lightest, 2.0, 10.0, 2, 0.0, true).unwrap();
+        assert_eq!(disc.len(), 2);
+        assert!(state.find_body(lightest).is_some());
+        assert_eq!(state.bodies.len(), 4);
+    }
+
+    #[test]
+    fn installing_a_staged_scene_keeps_live_settings() {
+        let mut state = SimulationState::new();
+        let mut staged = state.staging_state();
+        crate::scenarios::load_by_name(&mut staged, "binary_star");
+        let (theta, bodies) = (staged.theta, staged.bodies.len());
+        // Changed while the scene was being built
+        state.orbit_info = true;
+        state.set_speed_multiplier(3.0).unwrap();
+
+        state.install_staged(staged);
+        assert_eq!(state.bodies.len(), bodies);
+        assert_eq!(state.theta, theta);
+        assert!(state.orbit_info);
+        assert_eq!(state.speed_multiplier, 3.0);
+    }
+
+    #[test]
+    fn clamping_setters_reject_out_of_range_values() {
+        let mut state = SimulationState::new();
+        assert!(state.set_force_exponent(0.4).is_err());
+        assert!(state.set_force_exponent(f64::NAN).is_err());
+        assert!(state.set_relativistic(true, Some(0.0)).is_err());
+        assert!(!state.relativistic);
+        assert!(state.set_max_bodies(0, None).is_err());
+        assert!(state.set_max_speed(Some(-1.0)).is_err());
+        let config = CollisionConfig { restitution: 1.5, ..CollisionConfig::default() };
+        assert!(config.validate().is_err());
+        assert!(CollisionConfig::default().validate().is_ok());
+
+        assert!(state.set_force_exponent(3.0).is_ok());
+        assert_eq!(state.set_max_bodies(7, None), Ok(7));
+        assert!(state.set_max_speed(None).is_ok());
     }
 }
@@ -0,0 +1,40 @@
+use orbitforge_lib::{load_by_name, SimulationState};
+
+fn run(state: &mut SimulationState, steps: usize) {
+    for _ in 0..steps {
+        let collisions = state.step_once();
+        assert!(collisions.is_empty(), "the round trip only holds without collisions");
+    }
+}
+
+#[test]
+fn reversed_run_retraces_its_path() {
+    for scenario in ["figure_eight", "inner_solar", "binary_star"] {
+        let mut state = SimulationState::new();
+        assert!(load_by_name(&mut state, scenario));
+        let start: Vec<_> = state.bodies.iter().map(|b| (b.position, b.velocity)).collect();
+        // The farthest any body gets, so the tolerance scales with the scene
+        let scale = start.iter().map(|(p, _)| p.magnitude()).fold(1.0, f64::max);
+
+        run(&mut state, 500);
+        assert_eq!(state.reverse(), None);
+        run(&mut state, 500);
+
+        for (body, (position, velocity)) in state.bodies.iter().zip(&start) {
+            let drift = (body.position - *position).magnitude();
+            assert!(drift < 1e-9 * scale, "{scenario}: {} drifted {drift}", body.name);
+            let speed = (body.velocity + *velocity).magnitude();
+            let tolerance = 1e-9 * velocity.magnitude().max(1.0);
+            assert!(speed < tolerance, "{scenario}: {} came back at {speed} off", body.name);
+        }
+    }
+}
+
+#[test]
+fn reversing_with_velocity_dependent_forces_warns() {
+    let mut state = SimulationState::new();
+    assert!(load_by_name(&mut state, "sun_earth"));
+    state.relativistic = true;
+    let warning = state.reverse().expect("post-Newtonian gravity isn't reversible");
+    assert!(warning.contains("post-Newtonian"));
+}
@@ -102,6 +102,7 @@ export default function App() {
       invoke<{ x: number; y: number; z: number }[]>("predict_orbit", {
         bodyId: selectedBodyId,
         steps: 500,
+        includeThrust: true,
       })
         .then((path) => renderer.setPredictionPath(path))
         .catch(() => renderer.clearPrediction());
@@ -166,6 +167,7 @@ export default function App() {
       invoke<{ x: number; y: number; z: number }[]>("predict_orbit", {
         bodyId: selectedBodyId,
         steps: 500,
+        includeThrust: true,
       })
         .then((path) => renderer.setPredictionPath(path))
         .catch(() => {});
@@ -30,6 +30,7 @@ export function ControlPanel({
   const paused = useSimStore((s) => s.frame?.paused ?? false);
   const speed = useSimStore((s) => s.frame?.speed_multiplier ?? 1);
   const bodyCount = useSimStore((s) => s.frame?.bodies.length ?? 0);
+  const telemetry = useSimStore((s) => s.frame?.telemetry);
   const interactionMode = useSimStore((s) => s.interactionMode);
   const setInteractionMode = useSimStore((s) => s.setInteractionMode);
   const showLabels = useSimStore((s) => s.showLabels);
@@ -188,6 +189,12 @@ export function ControlPanel({
       <div className="flex items-center justify-between text-white/40 text-xs mt-2">
         <div>
           <div>Bodies: {bodyCount}</div>
+          {telemetry && (
+            <div>
+              Physics: {telemetry.compute_ms.toFixed(1)}ms, {telemetry.path}
+              {telemetry.path === "tree" && `, ${telemetry.tree_nodes} nodes`}
+            </div>
+          )}
           <div>Space=pause R=reset C=clear</div>
         </div>
         <SaveLoadButtons />
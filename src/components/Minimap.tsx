@@ -42,6 +42,7 @@ export function Minimap() {
       let minX = Infinity, maxX = -Infinity;
       let minY = Infinity, maxY = -Infinity;
       for (const body of frame.bodies) {
+        if (body.hidden) continue;
         minX = Math.min(minX, body.position.x);
         maxX = Math.max(maxX, body.position.x);
         minY = Math.min(minY, body.position.y);
@@ -56,6 +57,7 @@ export function Minimap() {
       const cy = (minY + maxY) / 2;
 
       for (const body of frame.bodies) {
+        if (body.hidden) continue;
         const x = SIZE / 2 + (body.position.x - cx) * scale;
         const y = SIZE / 2 - (body.position.y - cy) * scale;
         const r = Math.max(2, body.radius * scale * 0.5);
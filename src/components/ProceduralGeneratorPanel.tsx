@@ -10,6 +10,7 @@ export function ProceduralGeneratorPanel() {
   const [planetCount, setPlanetCount] = useState(6);
   const [minSpacing, setMinSpacing] = useState(120);
   const [maxRadius, setMaxRadius] = useState(1000);
+  const [maxEccentricity, setMaxEccentricity] = useState(0);
   const [expanded, setExpanded] = useState(false);
 
   const handleGenerate = useCallback(() => {
@@ -20,8 +21,9 @@ export function ProceduralGeneratorPanel() {
       planetCount,
       minSpacing,
       maxRadius,
+      eccentricity: { min: 0, max: maxEccentricity },
     }).catch(console.error);
-  }, [starMass, planetCount, minSpacing, maxRadius, setSelectedBody, setFollowBody]);
+  }, [starMass, planetCount, minSpacing, maxRadius, maxEccentricity, setSelectedBody, setFollowBody]);
 
   return (
     <div className="mt-2 pt-2 border-t border-white/10">
@@ -81,6 +83,18 @@ export function ProceduralGeneratorPanel() {
               className="w-full accent-blue-400"
             />
           </div>
+          <div>
+            <label className="text-white/50 block">Max Eccentricity: {maxEccentricity.toFixed(2)}</label>
+            <input
+              type="range"
+              min={0}
+              max={0.9}
+              step={0.05}
+              value={maxEccentricity}
+              onChange={(e) => setMaxEccentricity(parseFloat(e.target.value))}
+              className="w-full accent-blue-400"
+            />
+          </div>
           <button
             onClick={handleGenerate}
             className="w-full px-2 py-1.5 bg-purple-500/30 hover:bg-purple-500/50 rounded text-purple-200 transition-colors font-medium"
@@ -1,10 +1,17 @@
+import { useEffect } from "react";
 import { invoke } from "@tauri-apps/api/core";
 import { useSimStore } from "../store";
+import type { ScenarioInfo } from "../types";
 
 export function ScenarioSelector() {
   const scenarios = useSimStore((s) => s.scenarios);
   const setSelectedBody = useSimStore((s) => s.setSelectedBody);
   const setFollowBody = useSimStore((s) => s.setFollowBody);
+  const setScenarios = useSimStore((s) => s.setScenarios);
+
+  useEffect(() => {
+    invoke<ScenarioInfo[]>("list_scenarios").then(setScenarios).catch(console.error);
+  }, [setScenarios]);
 
   const handleLoad = async (id: string) => {
     try {
@@ -22,7 +29,7 @@ export function ScenarioSelector() {
         <button
           key={s.id}
           onClick={() => handleLoad(s.id)}
-          title={s.description}
+          title={`${s.description} (${s.body_count} bodies, ${s.planar ? "planar" : "3D"})`}
           className="px-2 py-1 bg-white/10 hover:bg-white/20 rounded text-xs transition-colors text-white/70 hover:text-white"
         >
           {s.name}
@@ -618,6 +618,9 @@ export class ThreeRenderer {
     let instanceIdx = 0;
 
     for (const body of this.frame.bodies) {
+      // Hidden bodies (dark-matter halo particles) pull on the others but
+      // aren't drawn; leaving them out of currentIds disposes any visuals
+      if (body.hidden) continue;
       currentIds.add(body.id);
 
       // Small bodies (asteroids) use instanced rendering
@@ -29,6 +29,7 @@ interface SimStore {
   placementZ: number;
   energyHistory: EnergyData[];
   setFrame: (frame: SimulationFrame) => void;
+  setScenarios: (scenarios: ScenarioInfo[]) => void;
   setSelectedBody: (id: number | null) => void;
   setFollowBody: (id: number | null) => void;
   setInteractionMode: (mode: InteractionMode) => void;
@@ -53,22 +54,29 @@ interface SimStore {
   updateMissionProgress: (progress: MissionProgress) => void;
 }
 
+// Frame LOD leaves distant bodies out of most frames; carry them over from
+// the previous frame, re-expressed against the new frame origin
+function withHeldBodies(frame: SimulationFrame, prev: SimulationFrame | null): SimulationFrame {
+  if (!frame.held?.length || !prev) return frame;
+  const held = new Set(frame.held);
+  const dx = prev.frame_origin.x - frame.frame_origin.x;
+  const dy = prev.frame_origin.y - frame.frame_origin.y;
+  const dz = prev.frame_origin.z - frame.frame_origin.z;
+  const carried = prev.bodies
+    .filter((b) => held.has(b.id))
+    .map((b) => ({
+      ...b,
+      position: { x: b.position.x + dx, y: b.position.y + dy, z: b.position.z + dz },
+    }));
+  return { ...frame, bodies: [...frame.bodies, ...carried] };
+}
+
 export const useSimStore = create<SimStore>((set) => ({
   frame: null,
   selectedBodyId: null,
   followBodyId: null,
   interactionMode: "select",
-  scenarios: [
-    { id: "sun_earth", name: "Sun & Earth", description: "Simple two-body orbit" },
-    { id: "inner_solar", name: "Inner Solar System", description: "Sun + Mercury, Venus, Earth, Mars" },
-    { id: "outer_solar", name: "Outer Solar System", description: "Sun + Jupiter, Saturn, Uranus, Neptune" },
-    { id: "full_solar", name: "Full Solar System", description: "Sun + all 8 planets" },
-    { id: "binary_star", name: "Binary Star", description: "Two stars orbiting their barycenter" },
-    { id: "figure_eight", name: "Figure-8", description: "Three-body periodic figure-8 solution" },
-    { id: "inclined_solar", name: "Inclined Solar", description: "Full solar system with 3D orbital inclinations" },
-    { id: "asteroid_belt", name: "Asteroid Belt", description: "Inner solar system with 200 asteroids" },
-    { id: "galaxy_collision", name: "Galaxy Collision", description: "Two galaxies colliding (600 particles)" },
-  ],
+  scenarios: [],
   showLabels: true,
   showVectors: false,
   showBarycenter: false,
@@ -89,11 +97,13 @@ export const useSimStore = create<SimStore>((set) => ({
   missionProgress: null,
   placementZ: 0,
   energyHistory: [],
-  setFrame: (frame) => set((s) => {
+  setFrame: (incoming) => set((s) => {
+    const frame = withHeldBodies(incoming, s.frame);
     const history = [...s.energyHistory, frame.energy];
     if (history.length > 300) history.splice(0, history.length - 300);
     return { frame, energyHistory: history };
   }),
+  setScenarios: (scenarios) => set({ scenarios }),
   setSelectedBody: (id) => set({ selectedBodyId: id }),
   setFollowBody: (id) => set({ followBodyId: id }),
   setInteractionMode: (mode) => set({ interactionMode: mode }),
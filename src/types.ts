@@ -9,6 +9,15 @@ export interface TrailPoint {
   y: number;
   z: number;
   speed: number;
+  time: number;
+  accel: number;
+}
+
+export interface Quat {
+  w: number;
+  x: number;
+  y: number;
+  z: number;
 }
 
 export type BodyType = "star" | "planet" | "spacecraft";
@@ -28,20 +37,80 @@ export interface CelestialBody {
   thrust: Vec3;
   fuel: number;
   max_fuel: number;
+  angular_velocity: Vec3;
+  orientation: Quat;
+  is_test_particle: boolean;
+  hidden: boolean;
+  record_trail_enabled: boolean;
+  trail_max: number;
+  nongrav: number | null;
+  group: string | null;
+  no_merge: boolean;
+  lifetime: number | null;
+}
+
+export interface BodyDetails extends CelestialBody {
+  speed: number;
 }
 
 export interface EnergyData {
   kinetic: number;
   potential: number;
   total: number;
+  virial_ratio: number;
+  // Excluding fixed bodies' kinetic energy and fixed-fixed potential
+  free_kinetic: number;
+  free_potential: number;
+  free_total: number;
+}
+
+export type AccelerationPath = "brute" | "tree" | "gpu" | "mesh";
+
+export interface Telemetry {
+  compute_ms: number;
+  path: AccelerationPath;
+  body_count: number;
+  tree_nodes: number;
+  tree_depth: number;
+  gpu_active: boolean;
+}
+
+export interface ActiveSolver {
+  path: AccelerationPath;
+  body_count: number;
+  gpu_initialized: boolean;
 }
 
 export interface SimulationFrame {
   bodies: CelestialBody[];
   tick: number;
+  sim_time: number;
   paused: boolean;
   speed_multiplier: number;
   energy: EnergyData;
+  ghost: CelestialBody | null;
+  focus_body: number | null;
+  frame_origin: Vec3;
+  frame_velocity: Vec3;
+  telemetry: Telemetry;
+  orbits: BodyOrbit[] | null;
+  // Bodies left out by frame LOD; hold them at their last known positions
+  held: number[];
+}
+
+export interface FrameLod {
+  distance: number;
+  every: number;
+}
+
+export type OrbitKind = "bound" | "parabolic" | "hyperbolic";
+
+export interface BodyOrbit {
+  body_id: number;
+  primary_id: number;
+  specific_energy: number;
+  kind: OrbitKind;
+  period: number | null;
 }
 
 export type InteractionMode = "select" | "place" | "slingshot";
@@ -51,10 +120,139 @@ export interface CollisionEvent {
   survivor_id: number;
   position: Vec3;
   combined_mass: number;
+  sim_time: number;
+}
+
+export interface StepResult {
+  frame: SimulationFrame;
+  collisions: CollisionEvent[];
+}
+
+export interface IdReassignment {
+  old_id: number;
+  new_id: number;
+  name: string;
+}
+
+export interface RepairReport {
+  reassigned: IdReassignment[];
+  next_id_raised: boolean;
+}
+
+// Planned burn for predict_orbit: delta_v is added at sim time `time`
+export interface ManeuverNode {
+  time: number;
+  delta_v: Vec3;
+}
+
+export interface StabilityReport {
+  stable: boolean;
+  drift: number;
+}
+
+export interface NumericalErrorEvent {
+  body_id: number;
+  name: string;
+  tick: number;
+  sim_time: number;
+}
+
+export interface SnapshotInfo {
+  tick: number;
+  sim_time: number;
+  body_count: number;
+}
+
+export interface ThetaReport {
+  theta: number;
+  requested: number;
+  brute_force: boolean;
+  warning: string | null;
+}
+
+export interface CameraBounds {
+  center: Vec3;
+  radius: number;
+}
+
+export interface ScenarioLoadedEvent {
+  name: string;
+  body_count: number;
+  bounds: CameraBounds;
+}
+
+export interface GenerationProgress {
+  name: string;
+  generated: number;
+  total: number;
+}
+
+export interface SpeedClampedEvent {
+  body_id: number;
+  name: string;
+  speed: number;
+  max_speed: number;
+  tick: number;
+  sim_time: number;
+}
+
+export interface CollisionStats {
+  total_collisions: number;
+  mass_accreted: number;
+  bodies_removed: number;
+}
+
+export interface PrecessionReport {
+  body_id: number;
+  primary_id: number;
+  orbits: number;
+  degrees_per_orbit: number;
+  period: number;
+}
+
+export interface SpeedRange {
+  min: number;
+  max: number;
 }
 
 export interface ScenarioInfo {
   id: string;
   name: string;
   description: string;
+  body_count: number;
+  planar: boolean;
 }
+
+export type ForceSource =
+  | { kind: "body"; id: number; name: string }
+  | { kind: "node"; center_of_mass: Vec3; mass: number; size: number };
+
+export interface AccelContribution {
+  source: ForceSource;
+  acceleration: Vec3;
+  magnitude: number;
+}
+
+export interface TreeBounds {
+  center: Vec3;
+  half_size: number;
+}
+
+export interface AccelerationBreakdown {
+  body_id: number;
+  path: AccelerationPath;
+  total: Vec3;
+  gravity: Vec3;
+  term_count: number;
+  contributors: AccelContribution[];
+}
+
+// Rejection value of commands that return Result<_, CommandError>
+export type CommandError =
+  | { kind: "body_not_found"; id: number }
+  | { kind: "not_a_spacecraft"; id: number }
+  | { kind: "unknown_scenario"; name: string }
+  | { kind: "body_limit"; max_bodies: number }
+  | { kind: "invalid_parameter"; message: string }
+  | { kind: "invalid_state"; message: string }
+  | { kind: "io"; message: string };